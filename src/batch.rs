@@ -0,0 +1,569 @@
+//! Batch jobs write one card per CSV row. The CSV file has a header row, columns `track1`,
+//! `track2` and `track3` hold track data and other columns are ignored. Empty cells and missing
+//! columns are treated like missing tracks in the write command.
+//!
+//! Progress of the job is kept in a [`BatchReport`], which is saved as a JSON state file after
+//! each row. Resumed jobs only write rows which are pending or were not reached yet, and are
+//! refused when the CSV file has changed since the job was started.
+
+use crate::data_format::DataFormat;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::track_action::TrackAction;
+use crate::tracks_data::TracksData;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TRACK_COLUMNS: [&str; 3] = ["track1", "track2", "track3"];
+// 64-bit FNV-1a
+const HASH_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const HASH_PRIME: u64 = 0x100000001b3;
+
+/// Row of the CSV file. Data is an error when the row contains invalid track data, such rows
+/// are skipped.
+#[derive(Debug)]
+pub struct BatchRow {
+    /// Line number in the CSV file, header is line 1
+    pub line: u64,
+    pub data: Result<TracksData, MsrxToolError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "message", rename_all = "lowercase")]
+pub enum RowOutcome {
+    Written,
+    Failed(String),
+    /// Row has invalid track data
+    Skipped(String),
+    /// Card was not swiped, or the job was stopped before the row. Written when job is resumed
+    Pending(String),
+}
+
+impl RowOutcome {
+    fn status(&self) -> &'static str {
+        match self {
+            RowOutcome::Written => "written",
+            RowOutcome::Failed(_) => "failed",
+            RowOutcome::Skipped(_) => "skipped",
+            RowOutcome::Pending(_) => "pending",
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            RowOutcome::Written => "",
+            RowOutcome::Failed(message)
+            | RowOutcome::Skipped(message)
+            | RowOutcome::Pending(message) => message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowResult {
+    pub line: u64,
+    #[serde(flatten)]
+    pub outcome: RowOutcome,
+    /// Seconds since Unix epoch
+    pub updated_at: u64,
+}
+
+/// What to do with a row after writing it failed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailureAction {
+    Retry,
+    Skip,
+    Quit,
+}
+
+/// Results of a batch job, also used as the state file of the job
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Number of rows in the CSV file, resumed job must have the same number of rows
+    pub total_rows: usize,
+    /// Hash of the CSV file, resumed job must have the same input. See [`input_hash`]
+    #[serde(default)]
+    pub input_hash: String,
+    /// Index of the first row which is pending or was not reached yet
+    pub next_row: usize,
+    /// Seconds since Unix epoch
+    pub started_at: u64,
+    /// Seconds since Unix epoch
+    pub updated_at: u64,
+    pub rows: Vec<RowResult>,
+}
+
+impl BatchReport {
+    pub fn load(path: &Path) -> Result<Self, MsrxToolError> {
+        let error =
+            |e: String| MsrxToolError::BatchStateFileError(format!("{}: {}", path.display(), e));
+        let content = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        serde_json::from_str(&content).map_err(|e| error(e.to_string()))
+    }
+
+    /// Saves the state to a temporary file first, so an interrupted save keeps the old state
+    pub fn save(&self, path: &Path) -> Result<(), MsrxToolError> {
+        let error = |e: std::io::Error| {
+            MsrxToolError::BatchStateFileError(format!("{}: {}", path.display(), e))
+        };
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let content =
+            serde_json::to_string_pretty(self).expect("State contains only serializable values");
+        fs::write(&temporary, content).map_err(error)?;
+        fs::rename(&temporary, path).map_err(error)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Pending(_)))
+    }
+
+    /// Row needs writing when it is pending or has no result yet
+    fn is_open(&self, line: u64) -> bool {
+        self.rows
+            .iter()
+            .find(|row| row.line == line)
+            .is_none_or(|row| matches!(row.outcome, RowOutcome::Pending(_)))
+    }
+
+    fn set_outcome(&mut self, line: u64, outcome: RowOutcome) {
+        self.updated_at = now();
+        let result = RowResult {
+            line,
+            outcome,
+            updated_at: self.updated_at,
+        };
+        match self.rows.iter_mut().find(|row| row.line == line) {
+            Some(row) => *row = result,
+            None => self.rows.push(result),
+        }
+    }
+
+    pub fn written(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Written))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Failed(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, RowOutcome::Skipped(_)))
+    }
+
+    fn count(&self, matches: impl Fn(&RowOutcome) -> bool) -> usize {
+        self.rows.iter().filter(|row| matches(&row.outcome)).count()
+    }
+
+    /// Writes the report as CSV with columns line, status and message
+    pub fn write_csv(&self, path: &Path) -> Result<(), MsrxToolError> {
+        let error = |e: csv::Error| MsrxToolError::ReportFileError(e.to_string());
+        let mut writer = csv::Writer::from_path(path).map_err(error)?;
+        writer
+            .write_record(["line", "status", "message"])
+            .map_err(error)?;
+        for row in &self.rows {
+            writer
+                .write_record([
+                    &row.line.to_string(),
+                    row.outcome.status(),
+                    row.outcome.message(),
+                ])
+                .map_err(error)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| MsrxToolError::ReportFileError(e.to_string()))
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Written: {}, failed: {}, skipped: {}, pending: {}",
+            self.written(),
+            self.failed(),
+            self.skipped(),
+            self.pending()
+        )?;
+        for row in self
+            .rows
+            .iter()
+            .filter(|row| row.outcome != RowOutcome::Written)
+        {
+            write!(
+                f,
+                "\nLine {} {}: {}",
+                row.line,
+                row.outcome.status(),
+                row.outcome.message()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses CSV rows into tracks. Errors in the header stop the job, errors in track data only
+/// make the row invalid.
+pub fn parse_csv(
+    text: &str,
+    data_format: DataFormat,
+    missing_tracks: TrackAction,
+) -> Result<Vec<BatchRow>, MsrxToolError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| MsrxToolError::InvalidCsvInput(e.to_string()))?;
+    let columns = TRACK_COLUMNS.map(|name| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(name))
+    });
+    if columns.iter().all(Option::is_none) {
+        return Err(MsrxToolError::InvalidCsvInput(
+            "header must have at least one of columns track1, track2, track3".to_string(),
+        ));
+    }
+
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|e| MsrxToolError::InvalidCsvInput(e.to_string()))?;
+        let line = record.position().map_or(0, |position| position.line());
+        let tracks = columns.map(|column| column.and_then(|column| record.get(column)));
+        let data = match data_format {
+            DataFormat::Iso => TracksData::from_iso_tracks(tracks),
+            DataFormat::Raw => TracksData::from_raw_tracks(tracks),
+        };
+        rows.push(BatchRow {
+            line,
+            data: data.map(|data| data.with_missing_tracks(missing_tracks)),
+        });
+    }
+    Ok(rows)
+}
+
+/// Identifies the CSV file of a job, so a job is not resumed with a changed file
+pub fn input_hash(text: &str) -> String {
+    let hash = text.bytes().fold(HASH_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(HASH_PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Writes rows which are still open in the report one by one and calls `on_update` after each
+/// row. When writing fails, `on_failure` decides whether the row is tried again, left, or the
+/// job is stopped. Rows left after a timeout and rows after stopping stay pending.
+pub fn run_job(
+    input_hash: &str,
+    rows: &[BatchRow],
+    report: &mut BatchReport,
+    mut write: impl FnMut(&BatchRow, &TracksData) -> Result<(), MsrxToolError>,
+    mut on_failure: impl FnMut(&BatchRow, &MsrxToolError) -> FailureAction,
+    mut on_update: impl FnMut(&BatchReport) -> Result<(), MsrxToolError>,
+) -> Result<(), MsrxToolError> {
+    if !report.rows.is_empty() && report.total_rows != rows.len() {
+        return Err(MsrxToolError::BatchStateMismatch(
+            report.total_rows,
+            rows.len(),
+        ));
+    }
+    if !report.rows.is_empty() && report.input_hash != input_hash {
+        return Err(MsrxToolError::BatchInputChanged);
+    }
+    report.total_rows = rows.len();
+    report.input_hash = input_hash.to_string();
+    if report.started_at == 0 {
+        report.started_at = now();
+    }
+
+    let mut stopped = false;
+    for row in rows {
+        if !report.is_open(row.line) {
+            continue;
+        }
+        let outcome = match &row.data {
+            _ if stopped => RowOutcome::Pending("Job stopped".to_string()),
+            Err(e) => RowOutcome::Skipped(e.to_string()),
+            Ok(data) => loop {
+                let error = match write(row, data) {
+                    Ok(()) => break RowOutcome::Written,
+                    Err(e) => e,
+                };
+                let action = on_failure(row, &error);
+                let outcome = match error {
+                    MsrxToolError::CardNotSwiped => RowOutcome::Pending(error.to_string()),
+                    _ => RowOutcome::Failed(error.to_string()),
+                };
+                match action {
+                    FailureAction::Retry => continue,
+                    FailureAction::Skip => break outcome,
+                    FailureAction::Quit => {
+                        stopped = true;
+                        break outcome;
+                    }
+                }
+            },
+        };
+        report.set_outcome(row.line, outcome);
+        report.next_row = rows
+            .iter()
+            .position(|row| report.is_open(row.line))
+            .unwrap_or(rows.len());
+        on_update(report)?;
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "name,track1,track2\n\
+        Alice,%ALICE?,;1001?\n\
+        Bob,,;1002?\n\
+        Carol,%CAROL?,;10A3?\n";
+
+    #[test]
+    fn test_parse_csv() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].line, 2);
+        let alice = rows[0].data.as_ref().unwrap();
+        assert_eq!(alice.track1.data, b"%ALICE?".to_vec());
+        assert_eq!(alice.track2.data, b";1001?".to_vec());
+        assert_eq!(alice.track3.action, TrackAction::Leave);
+        assert_eq!(
+            rows[1].data.as_ref().unwrap().track1.action,
+            TrackAction::Leave
+        );
+        assert!(matches!(
+            rows[2].data,
+            Err(MsrxToolError::InvalidTrackData(2, _))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_csv_quoted_and_missing_tracks() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(
+            "TRACK1,track3\n\"%A,B?\",\n",
+            DataFormat::Iso,
+            TrackAction::Erase,
+        )?;
+
+        let data = rows[0].data.as_ref().unwrap();
+        assert_eq!(data.track1.data, b"%A,B?".to_vec());
+        assert_eq!(data.track2.action, TrackAction::Erase);
+        assert_eq!(data.track3.action, TrackAction::Erase);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_csv_without_track_columns() {
+        assert!(matches!(
+            parse_csv("name\nAlice\n", DataFormat::Iso, TrackAction::Leave),
+            Err(MsrxToolError::InvalidCsvInput(_))
+        ));
+    }
+
+    fn run(
+        rows: &[BatchRow],
+        report: &mut BatchReport,
+        write: impl FnMut(&BatchRow, &TracksData) -> Result<(), MsrxToolError>,
+        on_failure: impl FnMut(&BatchRow, &MsrxToolError) -> FailureAction,
+    ) -> Result<usize, MsrxToolError> {
+        let mut updates = 0;
+        run_job(&input_hash(CSV), rows, report, write, on_failure, |_| {
+            updates += 1;
+            Ok(())
+        })?;
+        Ok(updates)
+    }
+
+    #[test]
+    fn test_run_job_retries_and_skips() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+        let mut attempts = 0;
+
+        let updates = run(
+            &rows,
+            &mut report,
+            |row, _| {
+                attempts += 1;
+                match (row.line, attempts) {
+                    (2, 1) => Err(MsrxToolError::CardNotSwiped),
+                    (3, _) => Err(MsrxToolError::WriteFailed),
+                    _ => Ok(()),
+                }
+            },
+            |row, _| match row.line {
+                2 => FailureAction::Retry,
+                _ => FailureAction::Skip,
+            },
+        )?;
+
+        assert_eq!(attempts, 3);
+        assert_eq!(updates, 3);
+        assert_eq!(report.written(), 1);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.next_row, 3);
+        let summary = report.to_string();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "Written: 1, failed: 1, skipped: 1, pending: 0");
+        assert_eq!(
+            lines[1],
+            "Line 3 failed: Write failed, device did not report success"
+        );
+        assert!(lines[2].starts_with("Line 4 skipped: Invalid data for track 2"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_job_quit_leaves_remaining_rows_pending() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+
+        run(
+            &rows,
+            &mut report,
+            |_, _| Err(MsrxToolError::WriteFailed),
+            |_, _| FailureAction::Quit,
+        )?;
+
+        assert_eq!(report.failed(), 1);
+        assert_eq!(
+            report.rows[1].outcome,
+            RowOutcome::Pending("Job stopped".to_string())
+        );
+        assert_eq!(report.next_row, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_writes_only_pending_rows() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+        run(
+            &rows,
+            &mut report,
+            |row, _| match row.line {
+                2 => Ok(()),
+                _ => Err(MsrxToolError::CardNotSwiped),
+            },
+            |_, _| FailureAction::Quit,
+        )?;
+        assert_eq!(report.pending(), 2);
+        assert_eq!(
+            report.rows[1].outcome,
+            RowOutcome::Pending(MsrxToolError::CardNotSwiped.to_string())
+        );
+        assert_eq!(report.next_row, 1);
+
+        let mut written = vec![];
+        run(
+            &rows,
+            &mut report,
+            |row, _| {
+                written.push(row.line);
+                Ok(())
+            },
+            |_, _| FailureAction::Quit,
+        )?;
+
+        assert_eq!(written, vec![3]);
+        assert_eq!(report.written(), 2);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.pending(), 0);
+        assert_eq!(report.next_row, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_with_different_rows() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+        run(
+            &rows,
+            &mut report,
+            |_, _| Ok(()),
+            |_, _| FailureAction::Quit,
+        )?;
+
+        assert_eq!(
+            run(
+                &rows[1..],
+                &mut report,
+                |_, _| Ok(()),
+                |_, _| { FailureAction::Quit }
+            ),
+            Err(MsrxToolError::BatchStateMismatch(3, 2))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_with_changed_input() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+        run(
+            &rows,
+            &mut report,
+            |_, _| Err(MsrxToolError::CardNotSwiped),
+            |_, _| FailureAction::Quit,
+        )?;
+        let changed = CSV.replace("1002", "2002");
+
+        assert_ne!(input_hash(&changed), report.input_hash);
+        assert_eq!(
+            run_job(
+                &input_hash(&changed),
+                &parse_csv(&changed, DataFormat::Iso, TrackAction::Leave)?,
+                &mut report,
+                |_, _| Ok(()),
+                |_, _| FailureAction::Quit,
+                |_| Ok(()),
+            ),
+            Err(MsrxToolError::BatchInputChanged)
+        );
+        assert_eq!(input_hash(""), "cbf29ce484222325");
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load_state() -> Result<(), MsrxToolError> {
+        let rows = parse_csv(CSV, DataFormat::Iso, TrackAction::Leave)?;
+        let mut report = BatchReport::default();
+        run(
+            &rows,
+            &mut report,
+            |_, _| Err(MsrxToolError::CardNotSwiped),
+            |_, _| FailureAction::Quit,
+        )?;
+        let path = std::env::temp_dir().join(format!("msrx-batch-{}.json", std::process::id()));
+
+        report.save(&path)?;
+        let content = fs::read_to_string(&path).unwrap();
+        let loaded = BatchReport::load(&path)?;
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, report);
+        assert!(content.contains(r#""status": "pending""#));
+        assert!(loaded.started_at > 0);
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 /// Module offers traits to convert chars to bits and vice versa depending on which track is being used
 /// Track 1 suppors wider range of characters than track 2 and track 3
 pub mod from_char;
+pub mod raw_track;
 pub mod to_char;
 use crate::msrx_tool_error::MsrxToolError;
 
@@ -0,0 +1,274 @@
+use crate::char_bits_conversion::from_char::FromChar;
+use crate::char_bits_conversion::to_char::ToChar;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::reverse_string::ReverseString;
+
+const TRACK_1_START_SENTINEL: char = '%';
+const TRACK2_3_START_SENTINEL: char = ';';
+const TRACK_END_SENTINEL: char = '?';
+// Track 1 characters are 6 bits from space, track 2 and 3 characters 4 bits from zero
+const TRACK1_CHARACTER_OFFSET: u8 = 0x20;
+const TRACK1_CHARACTER_MASK: u8 = 0x3f;
+const TRACK2_3_CHARACTER_OFFSET: u8 = 0x30;
+const TRACK2_3_CHARACTER_MASK: u8 = 0x0f;
+
+/// Converts raw bytes as they are on the stripe into a bit string, most significant bit first
+pub fn bytes_to_bits(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:08b}", byte)).collect()
+}
+
+/// Converts bit string into bytes, last byte is padded with zeros
+pub fn bits_to_bytes(bits: &str) -> Vec<u8> {
+    bits.as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (index, bit)| match bit {
+                    b'1' => byte | (0x80 >> index),
+                    _ => byte,
+                })
+        })
+        .collect()
+}
+
+/// Decodes raw track data into characters.
+///
+/// Leading zeros are skipped and decoding stops at the end sentinel or at trailing zeros.
+/// If the data doesn't start with a start sentinel, it's decoded once more in reverse
+/// direction in case the card was swiped the other way around.
+pub fn decode_track(
+    bytes: &[u8],
+    track_number: usize,
+    bits_per_character: u8,
+) -> Result<String, MsrxToolError> {
+    decode(bytes, track_number, bits_per_character).map(|(text, _)| text)
+}
+
+/// Checks the LRC character which follows the end sentinel. Tracks without data have no LRC.
+pub fn check_lrc(
+    bytes: &[u8],
+    track_number: usize,
+    bits_per_character: u8,
+) -> Result<(), MsrxToolError> {
+    let (text, rest) = decode(bytes, track_number, bits_per_character)?;
+    if text.is_empty() {
+        return Ok(());
+    }
+    let lrc_bits = match rest.get(..bits_per_character as usize) {
+        Some(bits) if text.ends_with(TRACK_END_SENTINEL) && bits.contains('1') => bits,
+        _ => return Err(MsrxToolError::LrcMissing(track_number)),
+    };
+    let read = match track_number {
+        1 => lrc_bits.from_track_1_bits(bits_per_character)?,
+        _ => lrc_bits.from_track_2_3_bits(bits_per_character)?,
+    };
+    let expected = lrc(&text, track_number)?;
+    if read == expected {
+        Ok(())
+    } else {
+        Err(MsrxToolError::LrcMismatch(track_number, expected, read))
+    }
+}
+
+/// Longitudinal redundancy check character of track characters including sentinels. Each data
+/// bit of the LRC is the XOR of the same bit of all characters, 6 bits on track 1 and 4 bits on
+/// tracks 2 and 3. The parity bit is added when the LRC is encoded like any other character.
+pub fn lrc(text: &str, track_number: usize) -> Result<char, MsrxToolError> {
+    let (offset, mask) = match track_number {
+        1 => (TRACK1_CHARACTER_OFFSET, TRACK1_CHARACTER_MASK),
+        _ => (TRACK2_3_CHARACTER_OFFSET, TRACK2_3_CHARACTER_MASK),
+    };
+    let lrc = text.chars().try_fold(0u8, |lrc, c| {
+        match u8::try_from(c).ok().and_then(|c| c.checked_sub(offset)) {
+            Some(value) if value <= mask => Ok(lrc ^ value),
+            _ => Err(MsrxToolError::BitConversionError),
+        }
+    })?;
+    Ok(char::from(offset + lrc))
+}
+
+/// Encodes characters into bit string (least significant bit first, parity bit last). Tracks
+/// which end with the end sentinel are followed by the LRC character.
+pub fn encode_track(text: &str, track_number: usize) -> Result<String, MsrxToolError> {
+    let encode = |c: char| match track_number {
+        1 => c.to_track_1_bits(),
+        _ => c.to_track_2_3_bits(),
+    };
+    let mut bits = text.chars().map(encode).collect::<Result<String, _>>()?;
+    if text.ends_with(TRACK_END_SENTINEL) {
+        bits.push_str(&encode(lrc(text, track_number)?)?);
+    }
+    Ok(bits)
+}
+
+/// Decodes track in the direction where it starts with the start sentinel. Returns the
+/// characters up to the end sentinel and the bits which follow it.
+fn decode(
+    bytes: &[u8],
+    track_number: usize,
+    bits_per_character: u8,
+) -> Result<(String, String), MsrxToolError> {
+    let bits = bytes_to_bits(bytes);
+    let start_sentinel = start_sentinel(track_number)?;
+
+    let forward = decode_bits(&bits, track_number, bits_per_character)?;
+    if forward.0.starts_with(start_sentinel) {
+        return Ok(forward);
+    }
+    let backward = decode_bits(&bits.reverse(), track_number, bits_per_character)?;
+    if backward.0.starts_with(start_sentinel) {
+        Ok(backward)
+    } else {
+        Ok(forward)
+    }
+}
+
+fn decode_bits(
+    bits: &str,
+    track_number: usize,
+    bits_per_character: u8,
+) -> Result<(String, String), MsrxToolError> {
+    if bits_per_character == 0 || bits_per_character > 8 {
+        return Err(MsrxToolError::BitConversionError);
+    }
+    let mut decoded = String::new();
+    let data_bits = bits.trim_start_matches('0');
+    let mut decoded_bits = 0;
+
+    for chunk in data_bits.as_bytes().chunks(bits_per_character as usize) {
+        if chunk.len() < bits_per_character as usize || chunk.iter().all(|&bit| bit == b'0') {
+            break;
+        }
+        let chunk = String::from_utf8_lossy(chunk);
+        let c = match track_number {
+            1 => chunk.from_track_1_bits(bits_per_character)?,
+            _ => chunk.from_track_2_3_bits(bits_per_character)?,
+        };
+        decoded.push(c);
+        decoded_bits += chunk.len();
+        if c == TRACK_END_SENTINEL {
+            break;
+        }
+    }
+
+    Ok((decoded, data_bits[decoded_bits..].to_string()))
+}
+
+fn start_sentinel(track_number: usize) -> Result<char, MsrxToolError> {
+    match track_number {
+        1 => Ok(TRACK_1_START_SENTINEL),
+        2 | 3 => Ok(TRACK2_3_START_SENTINEL),
+        _ => Err(MsrxToolError::BitConversionError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_and_bytes() {
+        assert_eq!(bytes_to_bits(&[0xaf, 0x01]), "1010111100000001");
+        assert_eq!(bits_to_bytes("1010111100000001"), vec![0xaf, 0x01]);
+        assert_eq!(bits_to_bytes("101"), vec![0xa0]);
+    }
+
+    #[test]
+    fn test_decode_track_3_swiped_backwards() -> Result<(), MsrxToolError> {
+        assert_eq!(decode_track(&[0xaf, 0xc2, 0xb0, 0x00], 3, 5)?, ";1?");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_and_decode_track_1() -> Result<(), MsrxToolError> {
+        let bits = format!("00000000{}", encode_track("%ABC 123?", 1)?);
+
+        assert_eq!(decode_track(&bits_to_bytes(&bits), 1, 7)?, "%ABC 123?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_and_decode_track_2() -> Result<(), MsrxToolError> {
+        let bits = encode_track(";0123456789=?", 2)?;
+
+        assert_eq!(decode_track(&bits_to_bytes(&bits), 2, 5)?, ";0123456789=?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_stops_at_end_sentinel() -> Result<(), MsrxToolError> {
+        // Track 2 ";1?" followed by LRC and trailing bits
+        let bits = format!("{}{}", encode_track(";1?", 2)?, "11000");
+        let bytes = bits_to_bytes(&bits);
+
+        assert_eq!(decode_track(&bytes, 2, 5)?, ";1?");
+        assert_eq!(check_lrc(&bytes, 2, 5), Ok(()));
+        // Trailing bits where the LRC should be
+        let bits = format!("{}{}", encode_track(";1", 2)?, "1111011000");
+        let bytes = bits_to_bytes(&bits);
+
+        assert_eq!(decode_track(&bytes, 2, 5)?, ";1?");
+        assert_eq!(
+            check_lrc(&bytes, 2, 5),
+            Err(MsrxToolError::LrcMismatch(2, '5', '3'))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lrc() -> Result<(), MsrxToolError> {
+        assert_eq!(lrc(";1?", 2)?, '5');
+        assert_eq!(lrc(";0123456789=?", 3)?, '8');
+        assert_eq!(lrc("%A?", 1)?, '[');
+        assert_eq!(lrc(";A?", 2), Err(MsrxToolError::BitConversionError));
+        Ok(())
+    }
+
+    #[test]
+    fn test_encoded_track_ends_with_lrc() -> Result<(), MsrxToolError> {
+        let bits = encode_track(";1?", 2)?;
+
+        assert_eq!(bits.len(), 4 * 5);
+        assert_eq!(&bits[15..], '5'.to_track_2_3_bits()?);
+        assert_eq!(encode_track(";1", 2)?.len(), 2 * 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_lrc() -> Result<(), MsrxToolError> {
+        let track1 = bits_to_bytes(&format!("00000000{}", encode_track("%ABC 123?", 1)?));
+        let track2 = bits_to_bytes(&encode_track(";0123456789=?", 2)?);
+
+        assert_eq!(check_lrc(&track1, 1, 7), Ok(()));
+        assert_eq!(check_lrc(&track2, 2, 5), Ok(()));
+        assert_eq!(check_lrc(&[], 3, 5), Ok(()));
+        // Track 1 bits for "%ABC 123?" followed by LRC of "%ABC 124?"
+        let wrong_lrc = lrc("%ABC 124?", 1)?;
+        let bits = format!(
+            "{}{}{}",
+            encode_track("%ABC 123", 1)?,
+            '?'.to_track_1_bits()?,
+            wrong_lrc.to_track_1_bits()?
+        );
+        let bytes = bits_to_bytes(&bits);
+        assert_eq!(
+            check_lrc(&bytes, 1, 7),
+            Err(MsrxToolError::LrcMismatch(
+                1,
+                lrc("%ABC 123?", 1)?,
+                wrong_lrc
+            ))
+        );
+        // Swiped backwards, LRC still follows the end sentinel when the bits are reversed
+        assert_eq!(check_lrc(&[0xaf, 0xc2, 0xb0, 0x00], 3, 5), Ok(()));
+        let without_lrc = format!("{}{}", encode_track(";1", 2)?, '?'.to_track_2_3_bits()?);
+        assert_eq!(
+            check_lrc(&bits_to_bytes(&without_lrc), 2, 5),
+            Err(MsrxToolError::LrcMissing(2))
+        );
+        Ok(())
+    }
+}
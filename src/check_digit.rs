@@ -0,0 +1,51 @@
+/// Luhn (mod 10) check digit for a string of decimal digits, `None` if there are other characters
+pub fn luhn_check_digit(digits: &str) -> Option<u8> {
+    let sum = luhn_sum(digits, true)?;
+    Some(((10 - sum % 10) % 10) as u8)
+}
+
+/// Checks number whose last digit is a Luhn check digit
+pub fn luhn_is_valid(number: &str) -> bool {
+    !number.is_empty() && luhn_sum(number, false).is_some_and(|sum| sum % 10 == 0)
+}
+
+/// Sums digits from right to left doubling every second digit. When the check digit is still
+/// missing, doubling starts from the rightmost digit.
+fn luhn_sum(digits: &str, double_first: bool) -> Option<u32> {
+    digits
+        .chars()
+        .rev()
+        .enumerate()
+        .try_fold(0, |sum, (index, c)| {
+            let digit = c.to_digit(10)?;
+            let digit = if (index % 2 == 0) == double_first {
+                let doubled = digit * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                digit
+            };
+            Some(sum + digit)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luhn_check_digit() {
+        assert_eq!(luhn_check_digit("7992739871"), Some(3));
+        assert_eq!(luhn_check_digit("411111111111111"), Some(1));
+        assert_eq!(luhn_check_digit("0"), Some(0));
+        assert_eq!(luhn_check_digit("12a"), None);
+    }
+
+    #[test]
+    fn test_luhn_is_valid() {
+        assert!(luhn_is_valid("79927398713"));
+        assert!(luhn_is_valid("4111111111111111"));
+        assert!(!luhn_is_valid("4111111111111112"));
+        assert!(!luhn_is_valid("4111=1111"));
+        assert!(!luhn_is_valid(""));
+    }
+}
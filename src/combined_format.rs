@@ -0,0 +1,115 @@
+//! Combined format puts all tracks on one line, separated by the format separator.
+//! Separator and backslash inside track data are escaped with a backslash, so with `_` as
+//! separator track `%A_B\C?` is written as `%A\_B\\C?`. Any other escape sequence is an error.
+
+use crate::msrx_tool_error::MsrxToolError;
+
+pub const ESCAPE_CHARACTER: char = '\\';
+
+pub fn validate_separator(separator: char) -> Result<(), MsrxToolError> {
+    if separator == ESCAPE_CHARACTER {
+        Err(MsrxToolError::InvalidFormatSeparator(separator))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn join(tracks: &[String], separator: char) -> String {
+    tracks
+        .iter()
+        .map(|track| escape(track, separator))
+        .collect::<Vec<String>>()
+        .join(&separator.to_string())
+}
+
+pub fn split(text: &str, separator: char) -> Result<Vec<String>, MsrxToolError> {
+    validate_separator(separator)?;
+
+    let mut tracks = vec![String::new()];
+    let mut chars = text.chars().enumerate();
+    while let Some((position, c)) = chars.next() {
+        if c == ESCAPE_CHARACTER {
+            match chars.next() {
+                Some((_, escaped)) if escaped == separator || escaped == ESCAPE_CHARACTER => {
+                    tracks.last_mut().unwrap().push(escaped)
+                }
+                _ => return Err(MsrxToolError::InvalidEscapeSequence(position)),
+            }
+        } else if c == separator {
+            tracks.push(String::new());
+        } else {
+            tracks.last_mut().unwrap().push(c);
+        }
+    }
+
+    Ok(tracks)
+}
+
+fn escape(track: &str, separator: char) -> String {
+    let mut escaped = String::with_capacity(track.len());
+    for c in track.chars() {
+        if c == separator || c == ESCAPE_CHARACTER {
+            escaped.push(ESCAPE_CHARACTER);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracks(tracks: &[&str]) -> Vec<String> {
+        tracks.iter().map(|track| track.to_string()).collect()
+    }
+
+    #[test]
+    fn test_join_escapes_separator_and_escape_character() {
+        let joined = join(&tracks(&["%A_B\\C?", ";1?", ""]), '_');
+
+        assert_eq!(joined, "%A\\_B\\\\C?_;1?_");
+    }
+
+    #[test]
+    fn test_split() -> Result<(), MsrxToolError> {
+        assert_eq!(
+            split("%A\\_B\\\\C?_;1?_", '_')?,
+            tracks(&["%A_B\\C?", ";1?", ""])
+        );
+        assert_eq!(split("%A_B?|;1?", '|')?, tracks(&["%A_B?", ";1?"]));
+        assert_eq!(split("", '_')?, tracks(&[""]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_invalid_escape_sequence() {
+        assert_eq!(
+            split("%A\\B?", '_'),
+            Err(MsrxToolError::InvalidEscapeSequence(2))
+        );
+        assert_eq!(
+            split("%AB?\\", '_'),
+            Err(MsrxToolError::InvalidEscapeSequence(4))
+        );
+    }
+
+    #[test]
+    fn test_escape_character_is_not_valid_separator() {
+        assert_eq!(
+            split("%A?", '\\'),
+            Err(MsrxToolError::InvalidFormatSeparator('\\'))
+        );
+    }
+
+    #[test]
+    fn test_join_split_round_trip() -> Result<(), MsrxToolError> {
+        let original = tracks(&["%__\\\\_?", ";12=34?", ";_\\|?"]);
+
+        for separator in ['_', '|', ';', '?', '%'] {
+            let joined = join(&original, separator);
+            assert_eq!(split(&joined, separator)?, original);
+        }
+        Ok(())
+    }
+}
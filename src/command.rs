@@ -1,3 +1,4 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Command {
     Reset,
     GetFirmwareVersion,
@@ -8,7 +9,13 @@ pub enum Command {
     SetLoCo,
     SetLeadingZeros,
     SetReadModeOnFormatISO,
+    SetReadModeOnFormatRaw,
     SetISOReadModeOn,
+    SetRawWriteModeOn,
+    Erase,
+    CommunicationTest,
+    SensorTest,
+    RamTest,
     TurnLedAllOn,
     TurnLedRedOn,
     TurnLedGreenOn,
@@ -27,7 +34,13 @@ impl Command {
             Command::SetLoCo => vec![0x1b, 0x79],
             Command::SetLeadingZeros => vec![0x1b, 0x7a],
             Command::SetReadModeOnFormatISO => vec![0x1b, 0x72],
+            Command::SetReadModeOnFormatRaw => vec![0x1b, 0x6d],
             Command::SetISOReadModeOn => vec![0x1b, 0x77],
+            Command::SetRawWriteModeOn => vec![0x1b, 0x6e],
+            Command::Erase => vec![0x1b, 0x63],
+            Command::CommunicationTest => vec![0x1b, 0x65],
+            Command::SensorTest => vec![0x1b, 0x86],
+            Command::RamTest => vec![0x1b, 0x87],
             Command::TurnLedAllOn => vec![0x1b, 0x82],
             Command::TurnLedRedOn => vec![0x1b, 0x85],
             Command::TurnLedGreenOn => vec![0x1b, 0x83],
@@ -1,4 +1,30 @@
-#[derive(Debug)]
+use crate::msrx_tool_error::MsrxToolError;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+const MIN_BPC: u8 = 5;
+const MAX_BPC: u8 = 8;
+const SUPPORTED_BPI: [u8; 2] = [75, 210];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coercivity {
+    High,
+    Low,
+}
+
+impl FromStr for Coercivity {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hi" => Ok(Coercivity::High),
+            "lo" => Ok(Coercivity::Low),
+            _ => Err(MsrxToolError::UnsupportedCoercivity),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TrackConfig {
     pub bpc: u8,
     pub bpi: u8,
@@ -6,19 +32,49 @@ pub struct TrackConfig {
     pub bpi210: u8,
 }
 impl TrackConfig {
-    pub fn bpi_packets(&self) -> Vec<u8> {
+    pub fn bpi_packets(&self, track_number: usize) -> Result<Vec<u8>, MsrxToolError> {
         match self.bpi {
-            75 => vec![self.bpi75].clone(),
-            210 => vec![self.bpi210].clone(),
-            _ => panic!("Invalid BPI"),
+            75 => Ok(vec![self.bpi75]),
+            210 => Ok(vec![self.bpi210]),
+            _ => Err(MsrxToolError::InvalidBpi(track_number, self.bpi)),
         }
     }
+
+    /// Deserializes track table, missing fields are filled from MSRX6 defaults of the track
+    fn deserialize<'de, const TRACK: usize, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TrackConfig, D::Error> {
+        let defaults = DeviceConfig::msrx6();
+        let defaults = [defaults.track1, defaults.track2, defaults.track3];
+        let track = &defaults[TRACK - 1];
+        let fields = TrackConfigFields::deserialize(deserializer)?;
+        Ok(TrackConfig {
+            bpc: fields.bpc.unwrap_or(track.bpc),
+            bpi: fields.bpi.unwrap_or(track.bpi),
+            bpi75: fields.bpi75.unwrap_or(track.bpi75),
+            bpi210: fields.bpi210.unwrap_or(track.bpi210),
+        })
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrackConfigFields {
+    bpc: Option<u8>,
+    bpi: Option<u8>,
+    bpi75: Option<u8>,
+    bpi210: Option<u8>,
+}
+
+/// Missing fields are filled from MSRX6 defaults when deserializing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct DeviceConfig {
+    #[serde(deserialize_with = "TrackConfig::deserialize::<1, _>")]
     pub track1: TrackConfig,
+    #[serde(deserialize_with = "TrackConfig::deserialize::<2, _>")]
     pub track2: TrackConfig,
+    #[serde(deserialize_with = "TrackConfig::deserialize::<3, _>")]
     pub track3: TrackConfig,
     pub leading_zero210: u8,
     pub leading_zero75: u8,
@@ -60,17 +116,246 @@ impl DeviceConfig {
         }
     }
 
-    pub fn bpc_packets(&self) -> Vec<u8> {
-        [self.track1.bpc, self.track2.bpc, self.track3.bpc]
+    /// Checks that values can be sent to the device
+    pub fn validate(&self) -> Result<(), MsrxToolError> {
+        for (index, track) in [&self.track1, &self.track2, &self.track3]
             .iter()
-            .cloned()
-            .collect::<Vec<u8>>()
+            .enumerate()
+        {
+            if !(MIN_BPC..=MAX_BPC).contains(&track.bpc) {
+                return Err(MsrxToolError::InvalidBpc(index + 1, track.bpc));
+            }
+            if !SUPPORTED_BPI.contains(&track.bpi) {
+                return Err(MsrxToolError::InvalidBpi(index + 1, track.bpi));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bpc_packets(&self) -> Vec<u8> {
+        [self.track1.bpc, self.track2.bpc, self.track3.bpc].to_vec()
     }
 
     pub fn leading_zero_packets(&self) -> Vec<u8> {
-        [self.leading_zero210, self.leading_zero75]
-            .iter()
-            .cloned()
-            .collect::<Vec<u8>>()
+        [self.leading_zero210, self.leading_zero75].to_vec()
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        DeviceConfig::msrx6()
+    }
+}
+
+/// Builds `DeviceConfig` from a base config, values are validated when `build` is called
+#[derive(Debug)]
+pub struct DeviceConfigBuilder {
+    base: DeviceConfig,
+    bpc: Option<Vec<u8>>,
+    bpi: Option<Vec<u8>>,
+    coercivity: Option<Coercivity>,
+    leading_zeros: Option<Vec<u8>>,
+}
+
+impl From<DeviceConfig> for DeviceConfigBuilder {
+    fn from(base: DeviceConfig) -> Self {
+        DeviceConfigBuilder {
+            base,
+            bpc: None,
+            bpi: None,
+            coercivity: None,
+            leading_zeros: None,
+        }
+    }
+}
+
+impl DeviceConfigBuilder {
+    /// Bits per character for tracks 1, 2 and 3
+    pub fn bpc(mut self, bpc: &[u8]) -> Self {
+        self.bpc = Some(bpc.to_vec());
+        self
+    }
+
+    /// Bits per inch for tracks 1, 2 and 3
+    pub fn bpi(mut self, bpi: &[u8]) -> Self {
+        self.bpi = Some(bpi.to_vec());
+        self
+    }
+
+    pub fn coercivity(mut self, coercivity: Coercivity) -> Self {
+        self.coercivity = Some(coercivity);
+        self
+    }
+
+    /// Leading zeros for 210 BPI tracks and for 75 BPI tracks
+    pub fn leading_zeros(mut self, leading_zeros: &[u8]) -> Self {
+        self.leading_zeros = Some(leading_zeros.to_vec());
+        self
+    }
+
+    pub fn build(self) -> Result<DeviceConfig, MsrxToolError> {
+        let mut config = self.base;
+
+        if let Some(bpc) = self.bpc {
+            let bpc = Self::per_track("BPC", &bpc)?;
+            config.track1.bpc = bpc[0];
+            config.track2.bpc = bpc[1];
+            config.track3.bpc = bpc[2];
+        }
+
+        if let Some(bpi) = self.bpi {
+            let bpi = Self::per_track("BPI", &bpi)?;
+            config.track1.bpi = bpi[0];
+            config.track2.bpi = bpi[1];
+            config.track3.bpi = bpi[2];
+        }
+
+        if let Some(coercivity) = self.coercivity {
+            config.is_hi_co = coercivity == Coercivity::High;
+        }
+
+        if let Some(leading_zeros) = self.leading_zeros {
+            match leading_zeros.as_slice() {
+                [leading_zero210, leading_zero75] => {
+                    config.leading_zero210 = *leading_zero210;
+                    config.leading_zero75 = *leading_zero75;
+                }
+                _ => return Err(MsrxToolError::InvalidLeadingZeros),
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn per_track(name: &str, values: &[u8]) -> Result<[u8; 3], MsrxToolError> {
+        match values {
+            [track1, track2, track3] => Ok([*track1, *track2, *track3]),
+            _ => Err(MsrxToolError::ValueRequiredForAllTracks(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_without_changes_is_msrx6() -> Result<(), MsrxToolError> {
+        let config = DeviceConfigBuilder::from(DeviceConfig::msrx6()).build()?;
+
+        assert_eq!(config.bpc_packets(), vec![7, 5, 5]);
+        assert_eq!(config.track1.bpi_packets(1)?, vec![0xa1]);
+        assert_eq!(config.track2.bpi_packets(2)?, vec![0xc0]);
+        assert_eq!(config.track3.bpi_packets(3)?, vec![0xd2]);
+        assert!(config.is_hi_co);
+        assert_eq!(config.leading_zero_packets(), vec![61, 22]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), MsrxToolError> {
+        let config = DeviceConfigBuilder::from(DeviceConfig::msrx6())
+            .bpc(&[8, 8, 8])
+            .bpi(&[75, 75, 75])
+            .coercivity(Coercivity::Low)
+            .leading_zeros(&[10, 20])
+            .build()?;
+
+        assert_eq!(config.bpc_packets(), vec![8, 8, 8]);
+        assert_eq!(config.track1.bpi_packets(1)?, vec![0xa0]);
+        assert_eq!(config.track2.bpi_packets(2)?, vec![0xc0]);
+        assert_eq!(config.track3.bpi_packets(3)?, vec![0x4b]);
+        assert!(!config.is_hi_co);
+        assert_eq!(config.leading_zero_packets(), vec![10, 20]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_invalid_values() {
+        let errors = [
+            (
+                DeviceConfigBuilder::from(DeviceConfig::msrx6())
+                    .bpc(&[7, 4, 5])
+                    .build(),
+                MsrxToolError::InvalidBpc(2, 4),
+            ),
+            (
+                DeviceConfigBuilder::from(DeviceConfig::msrx6())
+                    .bpc(&[7, 5])
+                    .build(),
+                MsrxToolError::ValueRequiredForAllTracks("BPC".to_string()),
+            ),
+            (
+                DeviceConfigBuilder::from(DeviceConfig::msrx6())
+                    .bpi(&[210, 75, 200])
+                    .build(),
+                MsrxToolError::InvalidBpi(3, 200),
+            ),
+            (
+                DeviceConfigBuilder::from(DeviceConfig::msrx6())
+                    .leading_zeros(&[1, 2, 3])
+                    .build(),
+                MsrxToolError::InvalidLeadingZeros,
+            ),
+        ];
+
+        for (result, error) in errors {
+            assert_eq!(result.unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn test_bpi_packets_unsupported_bpi() {
+        let track = TrackConfig {
+            bpi: 100,
+            ..DeviceConfig::msrx6().track1
+        };
+
+        assert_eq!(
+            track.bpi_packets(1).unwrap_err(),
+            MsrxToolError::InvalidBpi(1, 100)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_partial_config() -> Result<(), MsrxToolError> {
+        let config: DeviceConfig = toml::from_str(
+            r#"
+            vendor_id = 0x0802
+            is_hi_co = false
+
+            [track2]
+            bpc = 7
+            bpi = 210
+            bpi75 = 0xc0
+            bpi210 = 0xc1
+
+            [track3]
+            bpi = 75
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.vendor_id, 0x0802);
+        assert_eq!(config.product_id, 0x0003);
+        assert!(!config.is_hi_co);
+        assert_eq!(config.bpc_packets(), vec![7, 7, 5]);
+        assert_eq!(config.track2.bpi_packets(2)?, vec![0xc1]);
+        assert_eq!(config.track3.bpi_packets(3)?, vec![0x4b]);
+        assert!(toml::from_str::<DeviceConfig>(
+            "[track1]
+bcp = 7"
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = DeviceConfig::msrx6();
+        config.track3.bpc = 9;
+
+        assert_eq!(config.validate(), Err(MsrxToolError::InvalidBpc(3, 9)));
     }
 }
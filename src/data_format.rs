@@ -1,7 +1,9 @@
 use crate::msrx_tool_error::MsrxToolError;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DataFormat {
     Iso,
     Raw,
@@ -0,0 +1,331 @@
+use crate::data_format::DataFormat;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::parse::iso4909::Track3;
+use crate::redaction::MASK_CHARACTER;
+use crate::track_action::TrackAction;
+use crate::tracks_data::TracksData;
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Reads from STDIN instead of a file
+const STDIN_PATH: &str = "-";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    /// Tracks in one string, same as combined output format
+    Combined,
+    /// JSON document with `tracks` list, same as JSON output format
+    Json,
+}
+
+impl FromStr for InputFormat {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "combined" => Ok(InputFormat::Combined),
+            "json" => Ok(InputFormat::Json),
+            _ => Err(MsrxToolError::UnsupportedInputFormat),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonInput {
+    tracks: Vec<JsonInputTrack>,
+}
+
+/// Track in JSON input. Fields are the same as in JSON output, other output fields are ignored.
+/// With `action` the track can be left as is or erased instead of written.
+#[derive(Debug, Deserialize)]
+struct JsonInputTrack {
+    track: u8,
+    #[serde(default = "JsonInputTrack::default_action")]
+    action: TrackAction,
+    #[serde(default = "JsonInputTrack::default_present")]
+    present: bool,
+    format: Option<DataFormat>,
+    text: Option<String>,
+    raw: Option<String>,
+}
+
+impl JsonInputTrack {
+    fn default_present() -> bool {
+        true
+    }
+
+    fn default_action() -> TrackAction {
+        TrackAction::Write
+    }
+
+    fn format(&self, default_format: DataFormat) -> DataFormat {
+        match (self.format, &self.text, &self.raw) {
+            (Some(format), _, _) => format,
+            (None, Some(_), None) => DataFormat::Iso,
+            (None, None, Some(_)) => DataFormat::Raw,
+            _ => default_format,
+        }
+    }
+
+    fn data(&self, format: DataFormat) -> Result<&str, MsrxToolError> {
+        match format {
+            DataFormat::Iso => self.text.as_deref(),
+            DataFormat::Raw => self.raw.as_deref(),
+        }
+        .ok_or(MsrxToolError::MissingTrackData(self.track))
+    }
+}
+
+/// Reads whole input from a file, or from STDIN when path is `-`
+pub fn read_input(path: &Path) -> Result<String, MsrxToolError> {
+    let mut content = String::new();
+    if path == Path::new(STDIN_PATH) {
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| MsrxToolError::InputFileError(format!("STDIN: {}", e)))?;
+    } else {
+        content = fs::read_to_string(path)
+            .map_err(|e| MsrxToolError::InputFileError(format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Parses tracks from JSON document. Tracks can be given in any order and missing tracks
+/// are left as is. Data format of a track is taken from `format`, from the data field which
+/// is present, or from `default_format`.
+pub fn parse_json(text: &str, default_format: DataFormat) -> Result<TracksData, MsrxToolError> {
+    let input: JsonInput =
+        serde_json::from_str(text).map_err(|e| MsrxToolError::InvalidJsonInput(e.to_string()))?;
+
+    let mut tracks: [Option<&str>; 3] = [None, None, None];
+    let mut actions: [Option<TrackAction>; 3] = [None, None, None];
+    let mut data_format: Option<DataFormat> = None;
+    for track in input.tracks.iter().filter(|track| track.present) {
+        let index = match track.track {
+            1..=3 => usize::from(track.track - 1),
+            _ => return Err(MsrxToolError::InvalidTrackNumber(track.track)),
+        };
+        if actions[index].is_some() {
+            return Err(MsrxToolError::DuplicateTrack(track.track));
+        }
+        actions[index] = Some(track.action);
+        if track.action != TrackAction::Write {
+            continue;
+        }
+
+        let format = track.format(default_format);
+        if data_format.is_some_and(|data_format| data_format != format) {
+            return Err(MsrxToolError::MixedDataFormats);
+        }
+        data_format = Some(format);
+        tracks[index] = Some(track.data(format)?);
+    }
+
+    let mut tracks_data = match data_format.unwrap_or(default_format) {
+        DataFormat::Iso => TracksData::from_iso_tracks(tracks)?,
+        DataFormat::Raw => TracksData::from_raw_tracks(tracks)?,
+    };
+    for (track, action) in [
+        &mut tracks_data.track1,
+        &mut tracks_data.track2,
+        &mut tracks_data.track3,
+    ]
+    .into_iter()
+    .zip(actions)
+    {
+        if let Some(action) = action {
+            track.action = action;
+        }
+    }
+    Ok(tracks_data)
+}
+
+/// Parses ISO 4909 track 3 fields from JSON document. Fields are given alone or as JSON output
+/// of `read --parse iso4909`, where they are in `parsed.track3`. Masked fields are refused as
+/// the track can't be built from them.
+pub fn parse_track3_json(text: &str) -> Result<Track3, MsrxToolError> {
+    let invalid = |reason: String| MsrxToolError::InvalidJsonInput(reason);
+    let mut fields: serde_json::Value =
+        serde_json::from_str(text).map_err(|e| invalid(e.to_string()))?;
+    if let Some(parsed) = fields.get_mut("parsed") {
+        fields = parsed
+            .get_mut("track3")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| invalid("read output has no track 3 parsed as iso4909".to_string()))?;
+    }
+    if let Some(error) = fields.get("error") {
+        return Err(invalid(format!("track 3 was not parsed: {}", error)));
+    }
+    let masked = fields.as_object().is_some_and(|fields| {
+        fields
+            .values()
+            .filter_map(serde_json::Value::as_str)
+            .any(|value| value.contains(MASK_CHARACTER))
+    });
+    if masked {
+        return Err(invalid(
+            "track 3 fields are masked, read the card with --show-clear-data".to_string(),
+        ));
+    }
+    serde_json::from_value(fields).map_err(|e| invalid(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{self, OutputFormat};
+    use crate::parse::{ParseFormat, ParsedCard};
+    use crate::redaction::Redaction;
+
+    #[test]
+    fn test_parse_json_only_track2() -> Result<(), MsrxToolError> {
+        let tracks_data = parse_json(
+            r#"{"tracks": [{"track": 2, "text": ";1234?"}]}"#,
+            DataFormat::Iso,
+        )?;
+
+        assert_eq!(tracks_data.data_format()?, DataFormat::Iso);
+        assert_eq!(tracks_data.track1.action, TrackAction::Leave);
+        assert_eq!(tracks_data.track2.data, b";1234?".to_vec());
+        assert_eq!(tracks_data.track2.action, TrackAction::Write);
+        assert_eq!(tracks_data.track3.action, TrackAction::Leave);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_tracks_in_any_order() -> Result<(), MsrxToolError> {
+        let tracks_data = parse_json(
+            r#"{"tracks": [
+                {"track": 3, "text": ";5678?"},
+                {"track": 1, "text": "%A^B_C?"}
+            ]}"#,
+            DataFormat::Iso,
+        )?;
+
+        assert_eq!(tracks_data.track1.data, b"%A^B_C?".to_vec());
+        assert_eq!(tracks_data.track2.action, TrackAction::Leave);
+        assert_eq!(tracks_data.track3.data, b";5678?".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_read_output() -> Result<(), MsrxToolError> {
+        let output = r#"{"schema_version":1,"status":"ok","device":null,"tracks":[
+            {"track":1,"present":false,"format":"raw","text":null,"raw":""},
+            {"track":2,"present":true,"format":"raw","text":null,"raw":"00ff1b"},
+            {"track":3,"present":false,"format":"raw","text":null,"raw":""}]}"#;
+
+        let tracks_data = parse_json(output, DataFormat::Iso)?;
+
+        assert_eq!(tracks_data.data_format()?, DataFormat::Raw);
+        assert!(tracks_data.track1.data.is_empty());
+        assert_eq!(tracks_data.track2.data, vec![0x00, 0xff, 0x1b]);
+        assert!(tracks_data.track3.data.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_default_format() -> Result<(), MsrxToolError> {
+        let tracks_data = parse_json(
+            r#"{"tracks": [{"track": 1, "text": "%A?", "raw": "ff"}]}"#,
+            DataFormat::Raw,
+        )?;
+
+        assert_eq!(tracks_data.track1.data, vec![0xff]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_track_actions() -> Result<(), MsrxToolError> {
+        let tracks_data = parse_json(
+            r#"{"tracks": [
+                {"track": 1, "action": "erase"},
+                {"track": 2, "action": "leave", "text": ";1?"},
+                {"track": 3, "action": "write", "text": ";5?"}
+            ]}"#,
+            DataFormat::Iso,
+        )?;
+
+        assert_eq!(tracks_data.track1.action, TrackAction::Erase);
+        assert_eq!(tracks_data.track2.action, TrackAction::Leave);
+        assert!(tracks_data.track2.data.is_empty());
+        assert_eq!(tracks_data.track3.action, TrackAction::Write);
+        assert_eq!(
+            tracks_data.to_data_block()?,
+            b"\x1b\x73\x1b\x01\x1b\x035\x3f\x1c".to_vec()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_json_errors() {
+        let errors = [
+            (
+                r#"{"tracks": [{"track": 4, "text": ";1?"}]}"#,
+                MsrxToolError::InvalidTrackNumber(4),
+            ),
+            (
+                r#"{"tracks": [{"track": 2, "text": ";1?"}, {"track": 2, "text": ";2?"}]}"#,
+                MsrxToolError::DuplicateTrack(2),
+            ),
+            (
+                r#"{"tracks": [{"track": 1, "text": "%A?"}, {"track": 2, "raw": "ff"}]}"#,
+                MsrxToolError::MixedDataFormats,
+            ),
+            (
+                r#"{"tracks": [{"track": 2, "format": "iso", "raw": "ff"}]}"#,
+                MsrxToolError::MissingTrackData(2),
+            ),
+            (
+                r#"{"tracks": [{"track": 2, "text": "1;?"}]}"#,
+                MsrxToolError::InvalidStartSentinel(2, ';'),
+            ),
+        ];
+
+        for (input, error) in errors {
+            assert_eq!(parse_json(input, DataFormat::Iso).unwrap_err(), error);
+        }
+        assert!(matches!(
+            parse_json("{", DataFormat::Iso),
+            Err(MsrxToolError::InvalidJsonInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_track3_json_read_output() -> Result<(), MsrxToolError> {
+        let track3 =
+            ";014111111111111111=84097825000125061233030000000001122=1=12345678==000000042?";
+        let tracks_data = TracksData::from_str(&format!("__{}", track3), &'_')?;
+        let parsed = ParsedCard::parse(&tracks_data, ParseFormat::Iso4909);
+        let read_output = |redaction: Redaction| {
+            output::format(
+                &tracks_data,
+                &OutputFormat::Json,
+                &None,
+                None,
+                Some(&parsed),
+                None,
+                &redaction,
+            )
+        };
+
+        assert_eq!(
+            parse_track3_json(&read_output(Redaction::clear()))?.to_track()?,
+            track3
+        );
+        assert_eq!(
+            parse_track3_json(&read_output(Redaction::default())),
+            Err(MsrxToolError::InvalidJsonInput(
+                "track 3 fields are masked, read the card with --show-clear-data".to_string()
+            ))
+        );
+        assert!(matches!(
+            parse_track3_json(r#"{"parsed": {"format": "iso7813", "track1": null}}"#),
+            Err(MsrxToolError::InvalidJsonInput(_))
+        ));
+        Ok(())
+    }
+}
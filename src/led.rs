@@ -0,0 +1,40 @@
+use crate::command::Command;
+use crate::msrx_tool_error::MsrxToolError;
+use std::str::FromStr;
+
+/// LEDs of the device. Only one color can be on at a time, unless all are turned on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Led {
+    All,
+    Red,
+    Green,
+    Yellow,
+    Off,
+}
+
+impl Led {
+    pub fn command(&self) -> Command {
+        match self {
+            Led::All => Command::TurnLedAllOn,
+            Led::Red => Command::TurnLedRedOn,
+            Led::Green => Command::TurnLedGreenOn,
+            Led::Yellow => Command::TurnLedYellowOn,
+            Led::Off => Command::TurnLedAllOff,
+        }
+    }
+}
+
+impl FromStr for Led {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Led::All),
+            "red" => Ok(Led::Red),
+            "green" => Ok(Led::Green),
+            "yellow" => Ok(Led::Yellow),
+            "off" => Ok(Led::Off),
+            _ => Err(MsrxToolError::UnsupportedLed),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::msrx_tool_error::MsrxToolError;
+use serde::Serialize;
+
+/// Result of the LRC check of a raw track
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LrcStatus {
+    Ok,
+    Missing,
+    Mismatch,
+    /// Track or LRC character could not be decoded
+    Invalid,
+}
+
+impl From<&Result<(), MsrxToolError>> for LrcStatus {
+    fn from(result: &Result<(), MsrxToolError>) -> Self {
+        match result {
+            Ok(()) => LrcStatus::Ok,
+            Err(MsrxToolError::LrcMissing(_)) => LrcStatus::Missing,
+            Err(MsrxToolError::LrcMismatch(..)) => LrcStatus::Mismatch,
+            Err(_) => LrcStatus::Invalid,
+        }
+    }
+}
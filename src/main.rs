@@ -1,29 +1,67 @@
+use batch::{BatchReport, FailureAction};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+mod batch;
 mod char_bits_conversion;
+mod check_digit;
+mod combined_format;
 mod command;
 mod config;
 mod device_data;
+mod input;
 mod msrx;
 mod msrx_tool_error;
 mod reverse_string;
 mod to_hex;
+mod track_action;
 mod track_data;
+mod track_selection;
 mod track_status;
 mod tracks_data;
+mod transport;
+mod verify;
 use clap::Parser;
 use msrx::MsrxDevice;
 mod data_format;
 use data_format::DataFormat;
 mod iso_data;
+mod led;
+mod lrc_status;
 mod original_device_data;
 mod output;
-use clap::CommandFactory;
+mod parse;
+mod profile;
+mod raw_data;
+mod redaction;
+mod self_test;
+mod serial_counter;
+mod template;
+use config::{Coercivity, DeviceConfig, DeviceConfigBuilder};
+use input::InputFormat;
+use led::Led;
+use lrc_status::LrcStatus;
 use msrx_tool_error::MsrxToolError;
 use msrx_tool_error::MsrxToolError::CardNotSwiped;
 use output::OutputFormat;
+use parse::{ParseFormat, ParsedCard};
+use profile::Profile;
+use redaction::Redaction;
+use self_test::SelfTest;
+use serial_counter::SerialCounter;
 use std::time::Duration;
+use template::Template;
+use track_action::TrackAction;
+use track_selection::TrackSelection;
+use track_status::TrackStatus;
 use tracks_data::TracksData;
+use transport::emulator::{Emulator, VirtualCard};
+use transport::rusb_transport::RusbTransport;
+use transport::session::{RecordingTransport, ReplayTransport};
+use transport::Transport;
 
 /// Simple tool for reading and writing data to magstripe devices
 ///
@@ -34,6 +72,8 @@ use tracks_data::TracksData;
 /// Codes:
 ///  1 - Generic error  
 ///  2 - Card not swiped/Timeout. Card was not swiped when expected
+///  3 - Self test failed
+///  4 - Verify failed. Data read back after writing differs from written data
 ///
 /// ## Allowed charaacters
 ///   Track 1: !"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ\^_
@@ -51,30 +91,202 @@ struct Args {
     /// Command to use: read
     #[clap(subcommand)]
     command: Option<CliCommand>,
-    #[clap(short, long, default_value = "iso")]
-    /// Data format to use: iso, raw
+    #[clap(short, long)]
+    /// Data format to use: iso, raw. Default: iso
     data_format: Option<DataFormat>,
-    #[clap(short, long, default_value = "combined")]
-    /// Output format: json or stdout
+    #[clap(short, long)]
+    /// Output format: json or combined. Default: combined
     output_format: Option<OutputFormat>,
-    #[clap(long, default_value = "_")]
-    /// Input/output format separator when using combined output format
+    #[clap(long)]
+    /// Input/output format separator when using combined output format. Default: _
+    /// Separator and backslash inside track data are escaped with backslash, e.g. %A\_B?
     format_separator: Option<char>,
-    #[clap(long, default_value = "20")]
-    /// Timeout in seconds for reading tracks
+    #[clap(long)]
+    /// Timeout in seconds for reading tracks. Default: 20
     read_timeout: Option<u64>,
-    #[clap(long, default_value = "20")]
-    /// Timeout in seconds for writing tracks
+    #[clap(long)]
+    /// Timeout in seconds for writing and erasing tracks. Default: 20
     write_timeout: Option<u64>,
+    #[clap(long)]
+    /// Device profile to use from $XDG_CONFIG_HOME/msrx-tool/profiles.toml. Command line options override profile values
+    profile: Option<String>,
+    #[clap(long, value_delimiter = ',', value_name = "T1,T2,T3")]
+    /// Bits per character for each track, 5-8. Default: 7,5,5
+    bpc: Option<Vec<u8>>,
+    #[clap(long, value_delimiter = ',', value_name = "T1,T2,T3")]
+    /// Bits per inch for each track, 75 or 210. Default: 210,75,210
+    bpi: Option<Vec<u8>>,
+    #[clap(long)]
+    /// Coercivity of the cards: hi, lo. Default: hi
+    coercivity: Option<Coercivity>,
+    #[clap(long, value_delimiter = ',', value_name = "210BPI,75BPI")]
+    /// Number of leading zeros for 210 BPI tracks and for 75 BPI tracks. Default: 61,22
+    leading_zeros: Option<Vec<u8>>,
+    #[clap(long)]
+    /// Show card data as it is. By default all but first six and last four digits of PANs are masked.
+    /// Raw data can't be redacted, so reading it requires --decode-bpc or this option
+    show_clear_data: bool,
+    #[clap(long, conflicts_with = "show_clear_data")]
+    /// Hide cardholder name in output
+    hide_name: bool,
+    #[clap(long, conflicts_with = "show_clear_data")]
+    /// Hide discretionary data in output
+    hide_discretionary_data: bool,
+    #[clap(skip)]
+    redaction: Redaction,
+    #[clap(long)]
+    /// Use LEDs to show state: yellow while waiting for a card, green on success and red on failure
+    led_feedback: bool,
+    #[clap(long)]
+    /// Use software emulator instead of real device, card is swiped automatically
+    emulator: bool,
+    #[clap(long, requires = "record_clear_data")]
+    /// Record all traffic between the tool and the device to a session file. The file contains
+    /// card data as it is, so --record-clear-data must be given as well
+    record: Option<PathBuf>,
+    #[clap(long)]
+    /// Acknowledge that the session file given with --record contains card data as it is.
+    /// Output is still redacted
+    record_clear_data: bool,
+    #[clap(long)]
+    /// Replay recorded session file instead of using real device
+    replay: Option<PathBuf>,
 }
 #[derive(Parser, Debug)]
 enum CliCommand {
     #[clap(name = "read")]
     /// Read all tracks
-    Read,
+    Read {
+        #[clap(long, value_delimiter = ',', num_args = 1, value_name = "T1,T2,T3")]
+        /// Decode raw data into characters using given bits per character for each track, e.g. 7,5,5.
+        /// Tracks whose LRC doesn't match are reported on STDERR
+        decode_bpc: Option<Vec<u8>>,
+        #[clap(long)]
+        /// Keep reading cards and print one line per swipe until interrupted with Ctrl+C.
+        /// With JSON output format each line is a JSON document
+        continuous: bool,
+        #[clap(long, requires = "continuous")]
+        /// Stop continuous reading after this many cards
+        count: Option<u64>,
+        #[clap(long)]
+        /// Split tracks into fields of a card standard: iso7813 (payment cards, tracks 1 and 2),
+        /// iso4909 (financial transaction cards, track 3) or aamva (driver licenses, track 3
+        /// read with 7 bits per character).
+        /// Raw data must be decoded with --decode-bpc
+        parse: Option<ParseFormat>,
+    },
     #[clap(name = "write")]
-    /// Write content to tracks. Use
-    Write { track_data: String },
+    /// Write content to tracks. With raw data format, tracks are given as hex bytes
+    Write {
+        #[clap(required_unless_present = "input")]
+        /// Tracks in combined format, or JSON document with --input-format json
+        track_data: Option<String>,
+        #[clap(short, long, conflicts_with = "track_data")]
+        /// Read track data from a file, use - for STDIN
+        input: Option<PathBuf>,
+        #[clap(long, default_value = "combined")]
+        /// Input format: combined or json. JSON uses the same tracks list as JSON output, so only some tracks can be given
+        input_format: InputFormat,
+        #[clap(long, default_value = "leave")]
+        /// What to do with tracks which are not given: leave or erase. JSON input can set action per track
+        missing_tracks: TrackAction,
+        #[clap(long)]
+        /// After writing, swipe the card again to read it back and compare to written data
+        verify: bool,
+        #[clap(long)]
+        /// Encode ISO tracks into raw data followed by LRC and write them with the raw write
+        /// command, 7 bits per character on track 1 and 5 on tracks 2 and 3
+        encode_raw: bool,
+    },
+    #[clap(name = "clone")]
+    /// Read a card once and write the same data to one or more cards. Empty tracks are erased on copies.
+    /// Failed copies can be retried, skipped or the run stopped
+    Clone {
+        #[clap(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+        /// Number of cards to write
+        copies: u32,
+        #[clap(long)]
+        /// Swipe each copy again to read it back and compare to the original
+        verify: bool,
+    },
+    #[clap(name = "batch-write")]
+    /// Write one card per row of a CSV file with columns track1, track2 and track3. Other
+    /// columns are ignored. Failed rows can be retried, skipped or the job stopped
+    BatchWrite {
+        /// CSV file with a header row. STDIN can't be used, retry prompts are answered from it
+        input: PathBuf,
+        #[clap(long, default_value = "leave")]
+        /// What to do with tracks which are empty or have no column: leave or erase
+        missing_tracks: TrackAction,
+        #[clap(long)]
+        /// After writing, swipe each card again to read it back and compare to written data
+        verify: bool,
+        #[clap(long)]
+        /// Save result of each row to a CSV file with columns line, status and message
+        report: Option<PathBuf>,
+        #[clap(long)]
+        /// State file which keeps progress of the job. Default: input file name with .state.json suffix
+        state: Option<PathBuf>,
+        #[clap(long)]
+        /// Continue the job from the state file, only pending rows and rows not reached yet are written
+        resume: bool,
+    },
+    #[clap(name = "serial-write")]
+    /// Write serially numbered cards from a template in combined format. Placeholders:
+    /// {serial} or zero padded {serial:08}, {luhn} check digit of the digits before it, and
+    /// variables given with --var, e.g. ";{serial:08}{luhn}={expiry}?"
+    SerialWrite {
+        /// Card template in combined format
+        template: String,
+        #[clap(long)]
+        /// File which keeps the last issued serial number, numbers are never issued twice
+        counter: PathBuf,
+        #[clap(long, default_value = "1")]
+        /// Number of cards to write
+        count: u64,
+        #[clap(long)]
+        /// First serial number. Default: 1, or the number after the last issued serial
+        start: Option<u64>,
+        #[clap(long = "var", value_name = "NAME=VALUE", value_parser = template::parse_variable)]
+        /// Value for a template variable, can be given many times
+        variables: Vec<(String, String)>,
+        #[clap(long, default_value = "leave")]
+        /// What to do with tracks which are empty in the template: leave or erase
+        missing_tracks: TrackAction,
+        #[clap(long)]
+        /// After writing, swipe each card again to read it back and compare to written data
+        verify: bool,
+    },
+    #[clap(name = "build-track3")]
+    /// Print ISO 4909 track 3 built from fields in a JSON document, in combined format for the
+    /// write command. Fields are the same as track3 in JSON output of read --parse iso4909,
+    /// which can be given as is when read with --show-clear-data. No device is needed
+    BuildTrack3 {
+        /// JSON file, use - for STDIN
+        input: PathBuf,
+    },
+    #[clap(name = "erase")]
+    /// Erase tracks
+    Erase {
+        #[clap(
+            long,
+            value_delimiter = ',',
+            default_value = "1,2,3",
+            value_name = "TRACKS"
+        )]
+        /// Tracks to erase, e.g. 1,3
+        tracks: Vec<u8>,
+    },
+    #[clap(name = "selftest")]
+    /// Run device self tests: communication, sensor (requires swiping a card) and RAM
+    SelfTest {
+        #[clap(long)]
+        /// Skip sensor test, so no card needs to be swiped
+        skip_sensor: bool,
+    },
+    #[clap(name = "led")]
+    /// Turn LEDs on or off: all, red, green, yellow, off
+    Led { led: Led },
     #[clap(name = "fw")]
     /// Print firmware of the device
     Firmware,
@@ -87,6 +299,8 @@ enum ExitCode {
     Success = 0,
     CardNotSwiped = 2,
     GenericError = 1,
+    SelfTestFailed = 3,
+    VerifyFailed = 4,
 }
 impl ExitCode {
     fn as_i32(&self) -> i32 {
@@ -95,19 +309,117 @@ impl ExitCode {
 }
 
 fn main() {
-    let args = Args::parse();
-    CliCommand::command()
-        .after_help("Some more text")
-        .get_matches();
+    let mut args = Args::parse();
 
-    let mut msrx_device = match MsrxDevice::init_msrx6() {
-        Ok(device) => device,
+    let profile = match &args.profile {
+        Some(name) => match Profile::load(name) {
+            Ok(profile) => profile,
+            Err(e) => {
+                handle_error(&e);
+                return;
+            }
+        },
+        None => Profile::default(),
+    };
+    args.apply_profile(&profile);
+    if let Err(e) = combined_format::validate_separator(args.format_separator.unwrap()) {
+        handle_error(&e);
+        return;
+    }
+    if let Some(CliCommand::BuildTrack3 { input }) = &args.command {
+        match build_track3(input, args.format_separator.unwrap()) {
+            Ok(text) => println!("{}", text),
+            Err(e) => handle_error(&e),
+        }
+        return;
+    }
+
+    let config = match device_config(&args, profile.device) {
+        Ok(config) => config,
         Err(e) => {
-            println!("Error: {}", e);
-            process::exit(1);
+            handle_error(&e);
+            return;
         }
     };
 
+    if let Some(path) = &args.replay {
+        match ReplayTransport::from_file(path) {
+            Ok(transport) => start(transport, config, &args),
+            Err(e) => handle_error(&e),
+        }
+    } else if args.emulator {
+        let emulator = Emulator::with_card(VirtualCard::default());
+        start(emulator, config, &args);
+    } else {
+        match RusbTransport::open(config.vendor_id, config.product_id) {
+            Ok(transport) => start(transport, config, &args),
+            Err(e) => {
+                println!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+impl Args {
+    /// Fills options which were not given on command line from profile and then from defaults
+    fn apply_profile(&mut self, profile: &Profile) {
+        self.data_format = self
+            .data_format
+            .or(profile.data_format)
+            .or(Some(DataFormat::Iso));
+        self.output_format = self
+            .output_format
+            .or(profile.output_format)
+            .or(Some(OutputFormat::Combined));
+        self.format_separator = self
+            .format_separator
+            .or(profile.format_separator)
+            .or(Some('_'));
+        self.read_timeout = self.read_timeout.or(profile.read_timeout).or(Some(20));
+        self.write_timeout = self.write_timeout.or(profile.write_timeout).or(Some(20));
+        self.redaction = if self.show_clear_data {
+            Redaction::clear()
+        } else {
+            Redaction {
+                mask_pan: profile.redaction.mask_pan,
+                hide_name: profile.redaction.hide_name || self.hide_name,
+                hide_discretionary_data: profile.redaction.hide_discretionary_data
+                    || self.hide_discretionary_data,
+            }
+        };
+    }
+}
+
+fn device_config(args: &Args, base: DeviceConfig) -> Result<DeviceConfig, MsrxToolError> {
+    let mut builder = DeviceConfigBuilder::from(base);
+    if let Some(bpc) = &args.bpc {
+        builder = builder.bpc(bpc);
+    }
+    if let Some(bpi) = &args.bpi {
+        builder = builder.bpi(bpi);
+    }
+    if let Some(coercivity) = args.coercivity {
+        builder = builder.coercivity(coercivity);
+    }
+    if let Some(leading_zeros) = &args.leading_zeros {
+        builder = builder.leading_zeros(leading_zeros);
+    }
+    builder.build()
+}
+
+fn start<T: Transport>(transport: T, config: DeviceConfig, args: &Args) {
+    match &args.record {
+        Some(path) => match RecordingTransport::create(transport, path) {
+            Ok(recording) => run(&mut MsrxDevice::with_transport(recording, config), args),
+            Err(e) => handle_error(&e),
+        },
+        None => run(&mut MsrxDevice::with_transport(transport, config), args),
+    }
+}
+
+fn run<T: Transport>(msrx_device: &mut MsrxDevice<T>, args: &Args) {
+    msrx_device.led_feedback = args.led_feedback;
     match msrx_device.setup_device() {
         Ok(_) => {}
         Err(e) => {
@@ -116,52 +428,400 @@ fn main() {
     }
 
     match &args.command {
-        Some(CliCommand::Read) => {
+        Some(CliCommand::Read {
+            decode_bpc,
+            continuous,
+            count,
+            parse,
+        }) => {
             let timeout = Duration::from_secs(args.read_timeout.unwrap());
-            match msrx_device.read_tracks(&args.data_format.unwrap(), &timeout) {
-                Ok(result) => {
-                    println!(
-                        "{}",
-                        output::format(
-                            &result,
-                            &args.output_format.unwrap(),
-                            &args.format_separator,
-                        )
-                    );
+            let data_format = args.data_format.unwrap();
+            if data_format == DataFormat::Raw
+                && decode_bpc.is_none()
+                && args.redaction != Redaction::clear()
+            {
+                handle_error(&MsrxToolError::RawDataNotRedacted);
+            }
+            let output_format = args.output_format.unwrap();
+            let device_info = match output_format {
+                OutputFormat::Json => Some(msrx_device.device_info()),
+                OutputFormat::Combined => None,
+            };
+            let print_card = |result: TracksData| {
+                let (result, lrc) = match decode_bpc {
+                    Some(bpc) => match bpc.as_slice() {
+                        [track1, track2, track3] => {
+                            let bpc = [*track1, *track2, *track3];
+                            let lrc = result.check_lrc(&bpc);
+                            for error in lrc.iter().filter_map(|result| result.as_ref().err()) {
+                                eprintln!("{}", error);
+                            }
+                            let lrc = lrc.each_ref().map(LrcStatus::from);
+                            result.decode_raw(&bpc).map(|result| (result, Some(lrc)))
+                        }
+                        _ => Err(MsrxToolError::ValueRequiredForAllTracks(
+                            "Bits per character".to_string(),
+                        )),
+                    },
+                    None => Ok((result, None)),
+                }?;
+                let parsed = parse.map(|parse| ParsedCard::parse(&result, parse));
+                println!(
+                    "{}",
+                    output::format(
+                        &result,
+                        &output_format,
+                        &args.format_separator,
+                        device_info.as_ref(),
+                        parsed.as_ref(),
+                        lrc.as_ref(),
+                        &args.redaction,
+                    )
+                );
+                Ok(())
+            };
+
+            let result = if *continuous {
+                let running = Arc::new(AtomicBool::new(true));
+                let handler_running = running.clone();
+                let handler = ctrlc::set_handler(move || {
+                    if !handler_running.swap(false, Ordering::SeqCst) {
+                        process::exit(ExitCode::GenericError.as_i32());
+                    }
+                    eprintln!("Stopping after current read, press Ctrl+C again to exit now");
+                });
+                match handler {
+                    Ok(_) => msrx_device
+                        .read_cards(&data_format, &timeout, *count, &running, print_card)
+                        .map(|cards| eprintln!("Read {} cards", cards)),
+                    Err(e) => Err(MsrxToolError::InterruptHandlerError(e.to_string())),
                 }
-                Err(e) => handle_error(&e),
+            } else {
+                msrx_device
+                    .read_tracks(&data_format, &timeout)
+                    .and_then(print_card)
+            };
+            if let Err(e) = result {
+                handle_error(&e);
             }
         }
-        Some(CliCommand::Write { track_data }) => {
+        Some(CliCommand::Write {
+            track_data,
+            input,
+            input_format,
+            missing_tracks,
+            verify,
+            encode_raw,
+        }) => {
             let timeout = Duration::from_secs(args.write_timeout.unwrap());
             let separator = &args.format_separator.unwrap();
-            match TracksData::from_str(track_data, &separator) {
-                Ok(data) => match msrx_device.write_tracks(&data, &timeout) {
-                    Ok(_) => println!("Write operation successful"),
-                    Err(e) => handle_error(&e),
+            let data_format = args.data_format.unwrap();
+            let text = match input {
+                Some(path) => input::read_input(path),
+                None => Ok(track_data.clone().unwrap_or_default()),
+            };
+            let parsed = text.and_then(|text| match (input_format, data_format) {
+                (InputFormat::Json, _) => input::parse_json(&text, data_format),
+                (InputFormat::Combined, DataFormat::Iso) => TracksData::from_str(&text, separator),
+                (InputFormat::Combined, DataFormat::Raw) => {
+                    TracksData::from_raw_str(&text, separator)
+                }
+            });
+            let parsed = parsed.map(|data| data.with_missing_tracks(*missing_tracks));
+            let parsed = if *encode_raw {
+                parsed.and_then(|data| data.encode_raw())
+            } else {
+                parsed
+            };
+            let data = match parsed {
+                Ok(data) => data,
+                Err(e) => return handle_error(&e),
+            };
+            if let Err(e) = write_card(msrx_device, &data, &timeout, *verify, &args.redaction) {
+                handle_error(&e);
+            }
+        }
+
+        Some(CliCommand::Clone { copies, verify }) => {
+            let read_timeout = Duration::from_secs(args.read_timeout.unwrap());
+            let write_timeout = Duration::from_secs(args.write_timeout.unwrap());
+            println!("Swipe the card to copy");
+            let source = match msrx_device.read_tracks(&args.data_format.unwrap(), &read_timeout) {
+                Ok(data) if data.status == TrackStatus::Ok => data,
+                Ok(data) => return handle_error(&MsrxToolError::ReadFailed(data.status)),
+                Err(e) => return handle_error(&e),
+            };
+            let mut written = 0;
+            for copy in 1..=*copies {
+                loop {
+                    println!("Swipe blank card {}/{}", copy, copies);
+                    match write_card(
+                        msrx_device,
+                        &source,
+                        &write_timeout,
+                        *verify,
+                        &args.redaction,
+                    ) {
+                        Ok(()) => {
+                            written += 1;
+                            break;
+                        }
+                        Err(e) => {
+                            eprintln!("Copy {}/{} failed: {}", copy, copies, e);
+                            match prompt_failure_action() {
+                                FailureAction::Retry => continue,
+                                FailureAction::Skip => break,
+                                FailureAction::Quit => {
+                                    eprintln!("{} of {} copies were written", written, copies);
+                                    return handle_error(&e);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            println!("{} of {} copies were written", written, copies);
+            if written < *copies {
+                handle_error(&MsrxToolError::CloneIncomplete(copies - written));
+            }
+        }
+
+        Some(CliCommand::BatchWrite {
+            input,
+            missing_tracks,
+            verify,
+            report,
+            state,
+            resume,
+        }) => {
+            let timeout = Duration::from_secs(args.write_timeout.unwrap());
+            if input == Path::new("-") {
+                return handle_error(&MsrxToolError::BatchInputFromStdin);
+            }
+            let state_path = state.clone().unwrap_or_else(|| {
+                let mut path = input.clone().into_os_string();
+                path.push(".state.json");
+                PathBuf::from(path)
+            });
+            let result = if *resume {
+                BatchReport::load(&state_path)
+            } else if state_path.exists() {
+                Err(MsrxToolError::BatchStateExists(
+                    state_path.display().to_string(),
+                ))
+            } else {
+                Ok(BatchReport::default())
+            };
+            let rows = input::read_input(input).and_then(|text| {
+                batch::parse_csv(&text, args.data_format.unwrap(), *missing_tracks)
+                    .map(|rows| (batch::input_hash(&text), rows))
+            });
+            let ((input_hash, rows), mut result) = match (rows, result) {
+                (Ok(rows), Ok(result)) => (rows, result),
+                (Err(e), _) | (_, Err(e)) => return handle_error(&e),
+            };
+            let job = batch::run_job(
+                &input_hash,
+                &rows,
+                &mut result,
+                |row, data| {
+                    println!("Line {}: swipe a blank card", row.line);
+                    write_card(msrx_device, data, &timeout, *verify, &args.redaction)
+                },
+                |row, error| {
+                    eprintln!("Line {} failed: {}", row.line, error);
+                    prompt_failure_action()
                 },
+                |result| result.save(&state_path),
+            );
+            if let Err(e) = job {
+                return handle_error(&e);
+            }
+
+            println!("{}", result);
+            if let Some(path) = report {
+                if let Err(e) = result.write_csv(path) {
+                    return handle_error(&e);
+                }
+            }
+            let not_written = result.failed() + result.skipped() + result.pending();
+            if not_written > 0 {
+                handle_error(&MsrxToolError::BatchIncomplete(not_written));
+            }
+        }
+
+        Some(CliCommand::SerialWrite {
+            template,
+            counter,
+            count,
+            start,
+            variables,
+            missing_tracks,
+            verify,
+        }) => {
+            let timeout = Duration::from_secs(args.write_timeout.unwrap());
+            let template = Template::parse(template, variables.iter().cloned().collect());
+            let counter = SerialCounter::load(counter);
+            let (template, mut counter) = match (template, counter) {
+                (Ok(template), Ok(counter)) => (template, counter),
+                (Err(e), _) | (_, Err(e)) => return handle_error(&e),
+            };
+            let first = match counter.next(*start) {
+                Ok(first) => first,
+                Err(e) => return handle_error(&e),
+            };
+
+            for serial in first..first.saturating_add(*count) {
+                // Card is generated before its number is issued, so a template error doesn't
+                // use up the number
+                let data = template.tracks_data(
+                    serial,
+                    args.data_format.unwrap(),
+                    &args.format_separator.unwrap(),
+                );
+                let data = match data {
+                    Ok(data) => data.with_missing_tracks(*missing_tracks),
+                    Err(e) => return handle_error(&e),
+                };
+                // Number is issued before the card is swiped, so an interrupted run, retry or
+                // skip never issues it again
+                if let Err(e) = counter.save(serial) {
+                    return handle_error(&e);
+                }
+                loop {
+                    println!("Serial {}: swipe a blank card", serial);
+                    match write_card(msrx_device, &data, &timeout, *verify, &args.redaction) {
+                        Ok(()) => break,
+                        Err(e) => {
+                            eprintln!("Serial {} failed: {}", serial, e);
+                            match prompt_failure_action() {
+                                FailureAction::Retry => continue,
+                                FailureAction::Skip => break,
+                                FailureAction::Quit => return handle_error(&e),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(CliCommand::Erase { tracks }) => {
+            let timeout = Duration::from_secs(args.write_timeout.unwrap());
+            let result = TrackSelection::from_track_numbers(tracks)
+                .and_then(|selection| msrx_device.erase_tracks(&selection, &timeout));
+            match result {
+                Ok(TrackStatus::Ok) => println!("Erase operation successful"),
+                Ok(status) => handle_error(&MsrxToolError::EraseFailed(status)),
                 Err(e) => handle_error(&e),
             }
         }
 
-        Some(CliCommand::Firmware) => {
-            let firmware = msrx_device.get_firmware_version().unwrap();
-            println!("{}", firmware);
+        Some(CliCommand::SelfTest { skip_sensor }) => {
+            let mut all_passed = true;
+            for test in SelfTest::all() {
+                if *skip_sensor && test.requires_swipe() {
+                    println!("{}: skipped", test.name());
+                    continue;
+                }
+                if test.requires_swipe() {
+                    println!("{}: swipe a card", test.name());
+                }
+                let timeout = Duration::from_secs(args.read_timeout.unwrap());
+                let (passed, result) = match msrx_device.run_self_test(&test, &timeout) {
+                    Ok(true) => (true, "pass".to_string()),
+                    Ok(false) => (false, "FAIL".to_string()),
+                    Err(e) => (false, format!("FAIL ({})", e)),
+                };
+                all_passed &= passed;
+                println!("{}: {}", test.name(), result);
+            }
+            if !all_passed {
+                process::exit(ExitCode::SelfTestFailed.as_i32());
+            }
         }
 
-        Some(CliCommand::Model) => {
-            let model = msrx_device.get_model().unwrap();
-            println!("{}", model);
+        Some(CliCommand::Led { led }) => {
+            if let Err(e) = msrx_device.set_led(led) {
+                handle_error(&e);
+            }
         }
+
+        Some(CliCommand::Firmware) => match msrx_device.get_firmware_version() {
+            Ok(firmware) => println!("{}", firmware),
+            Err(e) => handle_error(&e),
+        },
+
+        Some(CliCommand::Model) => match msrx_device.get_model() {
+            Ok(model) => println!("{}", model),
+            Err(e) => handle_error(&e),
+        },
+        Some(CliCommand::BuildTrack3 { .. }) => unreachable!("track 3 is built without a device"),
         None => todo!(),
     }
 
     process::exit(ExitCode::Success.as_i32());
 }
 
+/// Builds track 3 from JSON fields, tracks 1 and 2 are empty so write leaves them as they are
+fn build_track3(input: &Path, separator: char) -> Result<String, MsrxToolError> {
+    let track3 = input::read_input(input)
+        .and_then(|text| input::parse_track3_json(&text))
+        .and_then(|track3| track3.to_track())?;
+    Ok(combined_format::join(
+        &[String::new(), String::new(), track3],
+        separator,
+    ))
+}
+
+/// Writes data and optionally reads the card again to verify it, mismatches are printed to STDERR
+fn write_card<T: Transport>(
+    msrx_device: &mut MsrxDevice<T>,
+    data: &TracksData,
+    timeout: &Duration,
+    verify: bool,
+    redaction: &Redaction,
+) -> Result<(), MsrxToolError> {
+    if !msrx_device.write_tracks(data, timeout)? {
+        return Err(MsrxToolError::WriteFailed);
+    }
+    println!("Write operation successful");
+
+    if verify {
+        println!("Swipe the card again to verify");
+        let mismatches = msrx_device.verify_tracks(data, timeout)?;
+        if !mismatches.is_empty() {
+            for mismatch in &mismatches {
+                eprintln!("{}", redaction.redact_mismatch(mismatch));
+            }
+            return Err(MsrxToolError::VerifyFailed(mismatches.len()));
+        }
+        println!("Verify successful");
+    }
+    Ok(())
+}
+
+/// Asks the operator what to do with a failed row. Enter retries, end of input leaves the row.
+fn prompt_failure_action() -> FailureAction {
+    loop {
+        eprint!("[r]etry, [s]kip or [q]uit? ");
+        let mut answer = String::new();
+        match io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return FailureAction::Skip,
+            Ok(_) => {}
+        }
+        match answer.trim() {
+            "" | "r" => return FailureAction::Retry,
+            "s" => return FailureAction::Skip,
+            "q" => return FailureAction::Quit,
+            _ => continue,
+        }
+    }
+}
+
 fn handle_error(error: &MsrxToolError) {
     let exit_code = match error {
         CardNotSwiped => ExitCode::CardNotSwiped,
+        MsrxToolError::VerifyFailed(_) => ExitCode::VerifyFailed,
         _ => ExitCode::GenericError,
     };
 
@@ -3,11 +3,19 @@ use crate::config::DeviceConfig;
 use crate::data_format::DataFormat;
 use crate::device_data::DeviceData;
 use crate::iso_data::IsoData;
+use crate::led::Led;
 use crate::msrx_tool_error::MsrxToolError;
 use crate::original_device_data::OriginalDeviceData;
-use crate::to_hex::ToHex;
+use crate::output::json::DeviceInfo;
+use crate::raw_data::RawData;
+use crate::self_test::SelfTest;
+use crate::track_selection::TrackSelection;
+use crate::track_status::TrackStatus;
 use crate::tracks_data::TracksData;
-use rusb::{Context, DeviceHandle, UsbContext};
+use crate::transport::rusb_transport::RusbTransport;
+use crate::transport::Transport;
+use crate::verify::{self, TrackMismatch};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 pub trait MSRX {
@@ -36,15 +44,9 @@ pub trait MSRX {
         command: &Command,
         timeout: &Duration,
     ) -> Result<bool, MsrxToolError>;
-    fn send_control_chunk(
-        &mut self,
-        endpoint: u8,
-        chunk: &Vec<u8>,
-        timeout: &Duration,
-    ) -> Result<(), MsrxToolError>;
     fn read_success(&mut self, endpoint: u8) -> Result<bool, MsrxToolError>;
 }
-impl MSRX for DeviceHandle<Context> {
+impl<T: Transport> MSRX for T {
     fn reset(&mut self, endpoint: u8) -> Result<bool, MsrxToolError> {
         self.run_command(endpoint, &Command::Reset, &Duration::from_secs(1))?;
         let result = self.read_success(endpoint)?;
@@ -57,8 +59,7 @@ impl MSRX for DeviceHandle<Context> {
         format: &DataFormat,
         timeout: u64,
     ) -> Result<DeviceData, MsrxToolError> {
-        let mut raw_data: [u8; 64] = [0; 64];
-        let _ = self.read_interrupt(endpoint, &mut raw_data, Duration::from_secs(timeout))?;
+        let raw_data = self.read_interrupt(endpoint, &Duration::from_secs(timeout))?;
 
         DeviceData::from_interrupt_data(raw_data, &format)
     }
@@ -68,8 +69,7 @@ impl MSRX for DeviceHandle<Context> {
         endpoint: u8,
         timeout: u64,
     ) -> Result<OriginalDeviceData, MsrxToolError> {
-        let mut raw_data: [u8; 64] = [0; 64];
-        let _ = self.read_interrupt(endpoint, &mut raw_data, Duration::from_secs(timeout))?;
+        let raw_data = self.read_interrupt(endpoint, &Duration::from_secs(timeout))?;
 
         raw_data.try_into()
     }
@@ -116,23 +116,6 @@ impl MSRX for DeviceHandle<Context> {
         Ok(())
     }
 
-    fn send_control_chunk(
-        &mut self,
-        endpoint: u8,
-        chunk: &Vec<u8>,
-        timeout: &Duration,
-    ) -> Result<(), MsrxToolError> {
-        let _ = self.write_control(
-            0x21,
-            9,
-            0x0300,
-            endpoint as u16,
-            &chunk,
-            Duration::from_secs(10),
-        )?;
-        Ok(())
-    }
-
     fn read_success(&mut self, endpoint: u8) -> Result<bool, MsrxToolError> {
         let raw_device_data = self.read_device_raw_interrupt(endpoint, 1)?;
 
@@ -141,35 +124,78 @@ impl MSRX for DeviceHandle<Context> {
 }
 
 #[derive(Debug)]
-pub struct MsrxDevice {
-    pub device_handle: DeviceHandle<Context>,
+pub struct MsrxDevice<T: Transport = RusbTransport> {
+    pub transport: T,
     pub config: DeviceConfig,
+    /// Show state of read, write and erase operations with LEDs
+    pub led_feedback: bool,
     interface: u8,
 }
 
-impl MsrxDevice {
-    pub fn init_msrx6() -> Result<MsrxDevice, MsrxToolError> {
+impl MsrxDevice<RusbTransport> {
+    pub fn init_msrx6() -> Result<MsrxDevice<RusbTransport>, MsrxToolError> {
         let config = DeviceConfig::msrx6();
-        // Initialize a USB context
-        let context = Context::new().expect("Failed to initialize USB context");
-
-        match context.open_device_with_vid_pid(config.vendor_id, config.product_id) {
-            Some(device_handle) => Ok(MsrxDevice {
-                device_handle,
-                config,
-                interface: 0,
-            }),
-            None => Err(MsrxToolError::DeviceNotFound),
+        let transport = RusbTransport::open(config.vendor_id, config.product_id)?;
+
+        Ok(MsrxDevice::with_transport(transport, config))
+    }
+
+    pub fn detach_kernel_driver(&mut self) -> Result<(), MsrxToolError> {
+        if self
+            .transport
+            .device_handle
+            .kernel_driver_active(self.interface)?
+        {
+            self.transport
+                .device_handle
+                .detach_kernel_driver(self.interface)?;
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn attach_kernel_driver(&mut self) -> Result<(), MsrxToolError> {
+        let kernel_active = self
+            .transport
+            .device_handle
+            .kernel_driver_active(self.interface)?;
+        if !kernel_active {
+            match self
+                .transport
+                .device_handle
+                .attach_kernel_driver(self.interface)
+            {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    println!("Error attaching kernel driver: {:?}", e);
+                    Err(MsrxToolError::Unknown)
+                }
+            }
+        } else {
+            println!("Kernel driver already active");
+            Ok(())
+        }
+    }
+}
+
+impl<T: Transport> MsrxDevice<T> {
+    pub fn with_transport(transport: T, config: DeviceConfig) -> MsrxDevice<T> {
+        MsrxDevice {
+            transport,
+            config,
+            led_feedback: false,
+            interface: 0,
         }
     }
 
     pub fn setup_device(&mut self) -> Result<(), MsrxToolError> {
-        self.device_handle.set_auto_detach_kernel_driver(true)?;
+        self.transport.set_auto_detach_kernel_driver(true)?;
 
         self.claim_interface()?;
 
         // Device setup
-        self.device_handle.reset()?;
+        self.transport.reset_device()?;
         self.init_device()?;
 
         Ok(())
@@ -183,49 +209,24 @@ impl MsrxDevice {
         Ok(())
     }
 
-    pub fn detach_kernel_driver(&mut self) -> Result<(), MsrxToolError> {
-        if self.device_handle.kernel_driver_active(self.interface)? {
-            self.device_handle.detach_kernel_driver(self.interface)?;
-            Ok(())
-        } else {
-            Ok(())
-        }
-    }
-
     pub fn claim_interface(&mut self) -> Result<(), MsrxToolError> {
-        self.device_handle.claim_interface(self.interface)?;
+        self.transport.claim_interface(self.interface)?;
         Ok(())
     }
 
     pub fn release_interface(&mut self) -> Result<(), MsrxToolError> {
-        let _ = self.device_handle.release_interface(self.interface)?;
+        self.transport.release_interface(self.interface)?;
         Ok(())
     }
 
-    pub fn attach_kernel_driver(&mut self) -> Result<(), MsrxToolError> {
-        let kernel_active = self.device_handle.kernel_driver_active(self.interface)?;
-        if !kernel_active {
-            match self.device_handle.attach_kernel_driver(self.interface) {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    println!("Error attaching kernel driver: {:?}", e);
-                    Err(MsrxToolError::Unknown)
-                }
-            }
-        } else {
-            println!("Kernel driver already active");
-            Ok(())
-        }
-    }
-
     pub fn set_bit_control_parity(&mut self) -> Result<(), MsrxToolError> {
-        self.device_handle.send_device_control(
+        self.transport.send_device_control(
             self.config.control_endpoint,
             &Command::SetBCP.with_payload(&self.config.bpc_packets()),
             &Duration::from_secs(1),
         )?;
         let result = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
         if result.data[1] == 0x1b
             && result.data[2] == 0x30
@@ -241,20 +242,20 @@ impl MsrxDevice {
 
     pub fn set_hico_loco_mode(&mut self) -> Result<(), MsrxToolError> {
         if self.config.is_hi_co {
-            self.device_handle.send_device_control(
+            self.transport.send_device_control(
                 self.config.control_endpoint,
                 &Command::SetHiCo.packets(),
                 &Duration::from_secs(1),
             )?;
         } else {
-            self.device_handle.send_device_control(
+            self.transport.send_device_control(
                 self.config.control_endpoint,
                 &Command::SetLoCo.packets(),
                 &Duration::from_secs(1),
             )?;
         }
         let result = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
 
         if result.data[1] == 0x1b && result.data[2] == 0x30 {
@@ -266,20 +267,20 @@ impl MsrxDevice {
 
     pub fn set_bit_per_inches(&mut self) -> Result<(), MsrxToolError> {
         for (index, packets) in [
-            &self.config.track1.bpi_packets(),
-            &self.config.track2.bpi_packets(),
-            &self.config.track3.bpi_packets(),
+            &self.config.track1.bpi_packets(1)?,
+            &self.config.track2.bpi_packets(2)?,
+            &self.config.track3.bpi_packets(3)?,
         ]
         .iter()
         .enumerate()
         {
-            self.device_handle.send_device_control(
+            self.transport.send_device_control(
                 self.config.control_endpoint,
                 &Command::SetBPI.with_payload(&packets),
                 &Duration::from_secs(1),
             )?;
             let result = self
-                .device_handle
+                .transport
                 .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
 
             if result.did_failed() {
@@ -291,13 +292,13 @@ impl MsrxDevice {
     }
 
     pub fn set_leading_zeros(&mut self) -> Result<(), MsrxToolError> {
-        self.device_handle.send_device_control(
+        self.transport.send_device_control(
             self.config.control_endpoint,
             &Command::SetLeadingZeros.with_payload(&self.config.leading_zero_packets()),
             &Duration::from_secs(1),
         )?;
         let result = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
         if result.did_failed() {
             return Err(MsrxToolError::ErrorSettingLeadingZeros);
@@ -306,41 +307,143 @@ impl MsrxDevice {
     }
 
     pub fn get_model(&mut self) -> Result<String, MsrxToolError> {
-        self.device_handle.run_command(
+        self.transport.run_command(
             self.config.control_endpoint,
             &Command::GetDeviceModel,
             &Duration::from_secs(1),
         )?;
         let raw_device_data = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
         Ok(raw_device_data.to_string())
     }
     pub fn reset(&mut self) -> Result<bool, MsrxToolError> {
-        self.device_handle.run_command(
+        self.transport.run_command(
             self.config.control_endpoint,
             &Command::Reset,
             &Duration::from_secs(1),
         )
         // let raw_device_data = self
-        //     .device_handle
+        //     .transport
         //     .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
         // dbg!(raw_device_data);
 
         // Ok(true)
     }
 
+    pub fn set_led(&mut self, led: &Led) -> Result<(), MsrxToolError> {
+        self.transport.run_command(
+            self.config.control_endpoint,
+            &led.command(),
+            &Duration::from_secs(1),
+        )?;
+        Ok(())
+    }
+
+    /// Runs an operation which waits for a card swipe. With LED feedback enabled, yellow LED is
+    /// on while waiting and green or red LED tells the result
+    fn with_led_feedback<R>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<R, MsrxToolError>,
+        succeeded: impl Fn(&R) -> bool,
+    ) -> Result<R, MsrxToolError> {
+        if !self.led_feedback {
+            return operation(self);
+        }
+        self.set_led(&Led::Yellow)?;
+        let result = operation(self);
+        self.show_result(result.as_ref().is_ok_and(&succeeded));
+
+        result
+    }
+
+    /// Shows result of an operation with green or red LED when LED feedback is enabled
+    fn show_result(&mut self, succeeded: bool) {
+        if self.led_feedback {
+            let led = if succeeded { Led::Green } else { Led::Red };
+            // Failing to set the LED shouldn't hide the result of the operation
+            let _ = self.set_led(&led);
+        }
+    }
+
     pub fn read_tracks(
         &mut self,
         format: &DataFormat,
         timeout: &Duration,
     ) -> Result<TracksData, MsrxToolError> {
+        self.with_led_feedback(
+            |device| device.read(format, timeout),
+            |tracks_data| tracks_data.status == TrackStatus::Ok,
+        )
+    }
+
+    /// Reads cards one after another until `count` cards are read or `running` is cleared. Each
+    /// read is passed to `on_card`. When no card is swiped before the timeout, only the read
+    /// command is sent again, so an idle device is not reset and LEDs don't show a failure.
+    /// Returns number of cards read.
+    pub fn read_cards(
+        &mut self,
+        format: &DataFormat,
+        timeout: &Duration,
+        count: Option<u64>,
+        running: &AtomicBool,
+        mut on_card: impl FnMut(TracksData) -> Result<(), MsrxToolError>,
+    ) -> Result<u64, MsrxToolError> {
+        let mut cards = 0;
+        let mut waiting = false;
+        while running.load(Ordering::SeqCst) && count.is_none_or(|count| cards < count) {
+            if self.led_feedback && !waiting {
+                self.set_led(&Led::Yellow)?;
+            }
+            waiting = true;
+            let tracks_data = match self.read_once(format, timeout) {
+                Ok(Some(tracks_data)) => tracks_data,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.show_result(false);
+                    return Err(e);
+                }
+            };
+            waiting = false;
+            self.show_result(tracks_data.status == TrackStatus::Ok);
+            cards += 1;
+            on_card(tracks_data)?;
+        }
+        if self.led_feedback && waiting {
+            let _ = self.set_led(&Led::Off);
+        }
+        Ok(cards)
+    }
+
+    /// Reads one card, on timeout the device is reset so it doesn't stay in read mode
+    fn read(
+        &mut self,
+        format: &DataFormat,
+        timeout: &Duration,
+    ) -> Result<TracksData, MsrxToolError> {
+        match self.read_once(format, timeout)? {
+            Some(tracks_data) => Ok(tracks_data),
+            None => {
+                let _ = self.reset();
+                self.init_device()?;
+
+                Err(MsrxToolError::CardNotSwiped)
+            }
+        }
+    }
+
+    /// Sends read command and waits for a card, `None` when no card was swiped before the timeout
+    fn read_once(
+        &mut self,
+        format: &DataFormat,
+        timeout: &Duration,
+    ) -> Result<Option<TracksData>, MsrxToolError> {
         let read_command = match format {
             DataFormat::Iso => Command::SetReadModeOnFormatISO,
-            DataFormat::Raw => return Err(MsrxToolError::UnsupportedDataFormatForReading),
+            DataFormat::Raw => Command::SetReadModeOnFormatRaw,
         };
 
-        self.device_handle.send_device_control(
+        self.transport.send_device_control(
             self.config.control_endpoint,
             &read_command.packets(),
             timeout,
@@ -354,11 +457,41 @@ impl MsrxDevice {
                         .map(|rd| IsoData { raw: rd.clone() })
                         .collect::<Vec<IsoData>>()
                         .try_into()?,
-                    DataFormat::Raw => return Err(MsrxToolError::UnsupportedDataFormatForReading),
+                    DataFormat::Raw => raw_datas
+                        .iter()
+                        .map(|rd| RawData { raw: *rd })
+                        .collect::<Vec<RawData>>()
+                        .try_into()?,
                 };
 
-                Ok(tracks_data)
+                Ok(Some(tracks_data))
             }
+            Err(MsrxToolError::DeviceError(rusb::Error::Timeout)) => Ok(None),
+            Err(_) => Err(MsrxToolError::Unknown),
+        }
+    }
+
+    pub fn write_tracks(
+        &mut self,
+        data: &TracksData,
+        timeout: &Duration,
+    ) -> Result<bool, MsrxToolError> {
+        self.with_led_feedback(|device| device.write(data, timeout), |success| *success)
+    }
+
+    fn write(&mut self, data: &TracksData, timeout: &Duration) -> Result<bool, MsrxToolError> {
+        let payload = &match data.data_format()? {
+            DataFormat::Iso => Command::SetISOReadModeOn.with_payload(&data.to_data_block()?),
+            DataFormat::Raw => Command::SetRawWriteModeOn.with_payload(&data.to_raw_data_block()?),
+        };
+
+        self.transport
+            .send_device_control(self.config.control_endpoint, &payload, &timeout)?;
+        match self
+            .transport
+            .read_device_raw_interrupt(self.config.interrupt_endpoint, timeout.as_secs())
+        {
+            Ok(raw_device_data) => Ok(raw_device_data.successful_operation()),
             Err(e) => match e {
                 MsrxToolError::DeviceError(rusb::Error::Timeout) => {
                     let _ = self.reset();
@@ -371,30 +504,54 @@ impl MsrxDevice {
         }
     }
 
-    pub fn write_tracks(
+    /// Reads the card again and compares it to written data, returns tracks which differ
+    pub fn verify_tracks(
         &mut self,
-        data: &TracksData,
+        written: &TracksData,
         timeout: &Duration,
-    ) -> Result<bool, MsrxToolError> {
-        dbg!(&data.to_data_block()?.to_hex());
-        let payload = &Command::SetISOReadModeOn.with_payload(&data.to_data_block()?);
+    ) -> Result<Vec<TrackMismatch>, MsrxToolError> {
+        let format = written.data_format()?;
+        self.with_led_feedback(
+            |device| {
+                let read = device.read(&format, timeout)?;
+                Ok(verify::compare(written, &read))
+            },
+            |mismatches| mismatches.is_empty(),
+        )
+    }
 
-        dbg!("moi");
-        dbg!(timeout);
-        dbg!(&payload.to_hex());
-        self.device_handle
-            .send_device_control(self.config.control_endpoint, &payload, &timeout)?;
+    /// Erases selected tracks and returns status reported by the device
+    pub fn erase_tracks(
+        &mut self,
+        selection: &TrackSelection,
+        timeout: &Duration,
+    ) -> Result<TrackStatus, MsrxToolError> {
+        self.with_led_feedback(
+            |device| device.erase(selection, timeout),
+            |status| *status == TrackStatus::Ok,
+        )
+    }
+
+    fn erase(
+        &mut self,
+        selection: &TrackSelection,
+        timeout: &Duration,
+    ) -> Result<TrackStatus, MsrxToolError> {
+        self.transport.send_device_control(
+            self.config.control_endpoint,
+            &Command::Erase.with_payload(&vec![selection.select_byte()?]),
+            timeout,
+        )?;
         match self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, timeout.as_secs())
         {
-            Ok(raw_device_data) => {
-                dbg!(raw_device_data.data.to_hex());
-                Ok(raw_device_data.successful_operation())
+            Ok(raw_device_data) if raw_device_data.data[1] == 0x1b => {
+                Ok(TrackStatus::from(raw_device_data.data[2]))
             }
+            Ok(_) => Ok(TrackStatus::Unknown),
             Err(e) => match e {
                 MsrxToolError::DeviceError(rusb::Error::Timeout) => {
-                    dbg!("err");
                     let _ = self.reset();
                     self.init_device()?;
 
@@ -405,6 +562,34 @@ impl MsrxDevice {
         }
     }
 
+    /// Runs one self test, returns `true` if the device reports it passed
+    pub fn run_self_test(
+        &mut self,
+        test: &SelfTest,
+        timeout: &Duration,
+    ) -> Result<bool, MsrxToolError> {
+        let timeout = if test.requires_swipe() {
+            *timeout
+        } else {
+            Duration::from_secs(1)
+        };
+        self.transport
+            .run_command(self.config.control_endpoint, &test.command(), &timeout)?;
+        match self
+            .transport
+            .read_device_raw_interrupt(self.config.interrupt_endpoint, timeout.as_secs())
+        {
+            Ok(raw_device_data) => Ok(test.passed(&raw_device_data)),
+            Err(MsrxToolError::DeviceError(rusb::Error::Timeout)) if test.requires_swipe() => {
+                let _ = self.reset();
+                self.init_device()?;
+
+                Err(MsrxToolError::CardNotSwiped)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     fn read_interrupts(
         &mut self,
         timeout: &Duration,
@@ -412,14 +597,14 @@ impl MsrxDevice {
         let mut raw_datas = vec![];
 
         let device_data: OriginalDeviceData = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, timeout.as_secs())?;
 
         raw_datas.push(device_data.clone());
         let mut is_last_packet = device_data.is_last_packet;
         while !is_last_packet {
             let raw_data = self
-                .device_handle
+                .transport
                 .read_device_raw_interrupt(self.config.interrupt_endpoint, timeout.as_secs())?;
 
             raw_datas.push(raw_data.clone());
@@ -430,15 +615,102 @@ impl MsrxDevice {
     }
 
     pub fn get_firmware_version(&mut self) -> Result<String, MsrxToolError> {
-        self.device_handle.run_command(
+        self.transport.run_command(
             self.config.control_endpoint,
             &Command::GetFirmwareVersion,
             &Duration::from_secs(1),
         )?;
         let raw_device_data = self
-            .device_handle
+            .transport
             .read_device_raw_interrupt(self.config.interrupt_endpoint, 1)?;
         let firmware = raw_device_data.to_string();
         Ok(firmware)
     }
+
+    /// Device metadata for output, model and firmware are left out if the device does not answer
+    pub fn device_info(&mut self) -> DeviceInfo {
+        let model = self.get_model().ok();
+        let firmware = self.get_firmware_version().ok();
+        DeviceInfo::new(&self.config, model, firmware)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Default)]
+    struct MockTransport {
+        sent_chunks: Vec<Vec<u8>>,
+        reports: VecDeque<[u8; 64]>,
+    }
+
+    impl MockTransport {
+        fn queue_report(&mut self, payload: &[u8]) {
+            let mut report = [0; 64];
+            report[0] = 0xc0 + payload.len() as u8;
+            report[1..1 + payload.len()].copy_from_slice(payload);
+            self.reports.push_back(report);
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn send_control_chunk(
+            &mut self,
+            _endpoint: u8,
+            chunk: &[u8],
+            _timeout: &Duration,
+        ) -> Result<(), MsrxToolError> {
+            self.sent_chunks.push(chunk.to_vec());
+            Ok(())
+        }
+
+        fn read_interrupt(
+            &mut self,
+            _endpoint: u8,
+            _timeout: &Duration,
+        ) -> Result<[u8; 64], MsrxToolError> {
+            self.reports
+                .pop_front()
+                .ok_or(MsrxToolError::DeviceError(rusb::Error::Timeout))
+        }
+    }
+
+    #[test]
+    fn test_send_device_control_splits_packets_to_chunks() -> Result<(), MsrxToolError> {
+        let mut transport = MockTransport::default();
+        let packets: Vec<u8> = (0..100).collect();
+
+        transport.send_device_control(0, &packets, &Duration::from_secs(1))?;
+
+        assert_eq!(transport.sent_chunks.len(), 2);
+        assert_eq!(transport.sent_chunks[0][0], 0x80 + 63);
+        assert_eq!(transport.sent_chunks[0][1..], packets[0..63]);
+        assert_eq!(transport.sent_chunks[1][0], 0xc0 + 37);
+        assert_eq!(transport.sent_chunks[1][1..], packets[63..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_setup_device_with_mock_transport() -> Result<(), MsrxToolError> {
+        let mut transport = MockTransport::default();
+        transport.queue_report(&[0x1b, 0x30, 7, 5, 5]);
+        transport.queue_report(&[0x1b, 0x30]);
+        transport.queue_report(&[0x1b, 0x30]);
+        transport.queue_report(&[0x1b, 0x30]);
+        transport.queue_report(&[0x1b, 0x30]);
+        transport.queue_report(&[0x1b, 0x30]);
+        let mut device = MsrxDevice::with_transport(transport, DeviceConfig::msrx6());
+
+        device.setup_device()?;
+
+        assert_eq!(
+            device.transport.sent_chunks[0],
+            vec![0xc5, 0x1b, 0x6f, 7, 5, 5]
+        );
+        assert_eq!(device.transport.sent_chunks[1], vec![0xc2, 0x1b, 0x78]);
+        assert!(device.transport.reports.is_empty());
+        Ok(())
+    }
 }
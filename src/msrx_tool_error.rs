@@ -1,3 +1,4 @@
+use crate::track_status::TrackStatus;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -12,14 +13,96 @@ pub enum MsrxToolError {
     ErrorSettingLeadingZeros,
     #[error("Bit conversion error")]
     BitConversionError,
+    #[error("LRC is missing on track {0}")]
+    LrcMissing(usize),
+    #[error("LRC mismatch on track {0}: expected {1}, read {2}")]
+    LrcMismatch(usize, char, char),
     #[error("device not found")]
     DeviceNotFound,
     #[error("unsupported data format")]
     UnsupportedDataFormat,
+    #[error("unsupported led, use one of: all, red, green, yellow, off")]
+    UnsupportedLed,
     #[error("unsupported output format")]
     UnsupportedOutputFormat,
-    #[error("format is not supported yet when reading tracks")]
-    UnsupportedDataFormatForReading,
+    #[error("{0} must be given for all three tracks")]
+    ValueRequiredForAllTracks(String),
+    #[error("Invalid BPC {1} for track {0}, allowed values are 5-8")]
+    InvalidBpc(usize, u8),
+    #[error("Invalid BPI {1} for track {0}, allowed values are 75 and 210")]
+    InvalidBpi(usize, u8),
+    #[error("Leading zeros must be given as two values: for 210 BPI tracks and for 75 BPI tracks")]
+    InvalidLeadingZeros,
+    #[error("unsupported coercivity, use hi or lo")]
+    UnsupportedCoercivity,
+    #[error(
+        "Invalid escape sequence at position {0}, only separator and backslash can be escaped"
+    )]
+    InvalidEscapeSequence(usize),
+    #[error("{0} cannot be used as format separator")]
+    InvalidFormatSeparator(char),
+    #[error("unsupported input format, use combined or json")]
+    UnsupportedInputFormat,
+    #[error("Failed to set Ctrl+C handler: {0}")]
+    InterruptHandlerError(String),
+    #[error("Read failed with status {0:?}")]
+    ReadFailed(TrackStatus),
+    #[error("Write failed, device did not report success")]
+    WriteFailed,
+    #[error("Verify failed, {0} track(s) differ from written data")]
+    VerifyFailed(usize),
+    #[error("unsupported track action, use leave, erase or write")]
+    UnsupportedTrackAction,
+    #[error("Invalid JSON input: {0}")]
+    InvalidJsonInput(String),
+    #[error("Track {0} is given more than once")]
+    DuplicateTrack(u8),
+    #[error("No data given for track {0} in its data format")]
+    MissingTrackData(u8),
+    #[error("Input file error: {0}")]
+    InputFileError(String),
+    #[error("Invalid CSV input: {0}")]
+    InvalidCsvInput(String),
+    #[error("Report file error: {0}")]
+    ReportFileError(String),
+    #[error("{0} rows were not written")]
+    BatchIncomplete(usize),
+    #[error("{0} copies were not written")]
+    CloneIncomplete(u32),
+    #[error("Batch state file error: {0}")]
+    BatchStateFileError(String),
+    #[error(
+        "Batch state file {0} already exists, use --resume to continue the job or delete the file"
+    )]
+    BatchStateExists(String),
+    #[error("Batch state file is for {0} rows but input has {1} rows")]
+    BatchStateMismatch(usize, usize),
+    #[error("Batch state file is for another input file")]
+    BatchInputChanged,
+    #[error("Batch input can't be read from STDIN, it is needed for answering retry prompts")]
+    BatchInputFromStdin,
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("Counter file error: {0}")]
+    CounterFileError(String),
+    #[error("Serial {0} was already issued, last issued serial is {1}")]
+    SerialAlreadyIssued(u64, u64),
+    #[error("unsupported parse format, use iso7813, iso4909 or aamva")]
+    UnsupportedParseFormat,
+    #[error("Invalid card data on track {0}: {1}")]
+    InvalidCardData(usize, String),
+    #[error("Profile file error: {0}")]
+    ProfileFileError(String),
+    #[error("Profile {0} not found")]
+    ProfileNotFound(String),
+    #[error("Could not find configuration directory")]
+    ConfigDirectoryNotFound,
+    #[error("Invalid raw data for track {0}")]
+    InvalidRawTrackData(usize),
+    #[error("Raw data for track {0} is too long: {1} bytes. Max length is {2}")]
+    RawDataForTrackIsTooLong(usize, usize, usize),
+    #[error("All tracks must use the same data format")]
+    MixedDataFormats,
     #[error("Couldn't convert track data to string")]
     InvalidUtf8DataInTrack,
     #[error("Card was not swiped")]
@@ -32,6 +115,22 @@ pub enum MsrxToolError {
     InvalidStartSentinel(usize, char),
     #[error("Invalid end sentinel for track {0}, expected {1}")]
     InvalidEndSentinel(usize, char),
+    #[error("Couldn't use session file: {0}")]
+    SessionFileError(String),
+    #[error("Invalid session file, line {0}")]
+    InvalidSessionFile(usize),
+    #[error("Replay doesn't match the recorded session, line {0}")]
+    ReplayMismatch(usize),
+    #[error("Recorded session ended")]
+    ReplaySessionEnded,
+    #[error("Invalid track number {0}, tracks are 1, 2 and 3")]
+    InvalidTrackNumber(u8),
+    #[error("No tracks selected")]
+    NoTracksSelected,
+    #[error("Erase failed, status: {0:?}")]
+    EraseFailed(TrackStatus),
+    #[error("Raw data can't be redacted, decode it with --decode-bpc or give --show-clear-data")]
+    RawDataNotRedacted,
     #[error("unknown conversion error")]
     Unknown,
 }
@@ -0,0 +1,195 @@
+use crate::config::DeviceConfig;
+use crate::data_format::DataFormat;
+use crate::lrc_status::LrcStatus;
+use crate::parse::ParsedCard;
+use crate::track_data::TrackData;
+use crate::track_status::TrackStatus;
+use crate::tracks_data::TracksData;
+use serde::Serialize;
+
+/// Increased when fields are removed or their meaning changes
+const SCHEMA_VERSION: u8 = 1;
+
+/// Device which produced the data. Model and firmware are missing if device did not answer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceInfo {
+    pub model: Option<String>,
+    pub firmware: Option<String>,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub hi_co: bool,
+    pub bpc: Vec<u8>,
+    pub bpi: Vec<u8>,
+}
+
+impl DeviceInfo {
+    pub fn new(config: &DeviceConfig, model: Option<String>, firmware: Option<String>) -> Self {
+        DeviceInfo {
+            model,
+            firmware,
+            vendor_id: config.vendor_id,
+            product_id: config.product_id,
+            hi_co: config.is_hi_co,
+            bpc: config.bpc_packets(),
+            bpi: vec![config.track1.bpi, config.track2.bpi, config.track3.bpi],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonDocument<'a> {
+    schema_version: u8,
+    status: TrackStatus,
+    device: Option<&'a DeviceInfo>,
+    tracks: Vec<JsonTrack>,
+    /// Fields of the card, only when parsing was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parsed: Option<&'a ParsedCard>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonTrack {
+    track: u8,
+    present: bool,
+    format: DataFormat,
+    /// Characters of the track, only for ISO data
+    text: Option<String>,
+    /// Track data bytes as hex
+    raw: String,
+    /// LRC check of raw data, only when raw data was decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lrc: Option<LrcStatus>,
+}
+
+impl JsonTrack {
+    fn new(track: u8, track_data: &TrackData, lrc: Option<LrcStatus>) -> Self {
+        JsonTrack {
+            track,
+            present: !track_data.data.is_empty(),
+            format: track_data.format,
+            text: match track_data.format {
+                DataFormat::Iso => Some(String::from_utf8_lossy(&track_data.data).to_string()),
+                DataFormat::Raw => None,
+            },
+            raw: hex::encode(&track_data.data),
+            lrc,
+        }
+    }
+}
+
+pub fn format_json(
+    tracks_data: &TracksData,
+    device: Option<&DeviceInfo>,
+    parsed: Option<&ParsedCard>,
+    lrc: Option<&[LrcStatus; 3]>,
+) -> String {
+    let lrc = |index: usize| lrc.map(|lrc| lrc[index]);
+    let document = JsonDocument {
+        schema_version: SCHEMA_VERSION,
+        status: tracks_data.status,
+        device,
+        tracks: vec![
+            JsonTrack::new(1, &tracks_data.track1, lrc(0)),
+            JsonTrack::new(2, &tracks_data.track2, lrc(1)),
+            JsonTrack::new(3, &tracks_data.track3, lrc(2)),
+        ],
+        parsed,
+    };
+
+    serde_json::to_string(&document).expect("JSON document contains only serializable values")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track_action::TrackAction;
+    use serde_json::{json, Value};
+
+    fn track(data: &[u8], format: DataFormat) -> TrackData {
+        TrackData {
+            data: data.to_vec(),
+            format,
+            action: TrackAction::for_data(data),
+        }
+    }
+
+    #[test]
+    fn test_format_json_iso() {
+        let tracks_data = TracksData {
+            track1: track(b"%ABC?", DataFormat::Iso),
+            track2: track(b"", DataFormat::Iso),
+            track3: track(b";123?", DataFormat::Iso),
+            status: TrackStatus::Ok,
+        };
+        let device = DeviceInfo::new(
+            &DeviceConfig::msrx6(),
+            Some("3S".to_string()),
+            Some("REVT3.12".to_string()),
+        );
+
+        let value: Value =
+            serde_json::from_str(&format_json(&tracks_data, Some(&device), None, None)).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "schema_version": 1,
+                "status": "ok",
+                "device": {
+                    "model": "3S",
+                    "firmware": "REVT3.12",
+                    "vendor_id": 0x0801,
+                    "product_id": 0x0003,
+                    "hi_co": true,
+                    "bpc": [7, 5, 5],
+                    "bpi": [210, 75, 210]
+                },
+                "tracks": [
+                    {"track": 1, "present": true, "format": "iso", "text": "%ABC?", "raw": "254142433f"},
+                    {"track": 2, "present": false, "format": "iso", "text": "", "raw": ""},
+                    {"track": 3, "present": true, "format": "iso", "text": ";123?", "raw": "3b3132333f"}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_format_json_raw_without_device() {
+        let tracks_data = TracksData {
+            track1: track(&[], DataFormat::Raw),
+            track2: track(&[0x00, 0xff, 0x1b], DataFormat::Raw),
+            track3: track(&[], DataFormat::Raw),
+            status: TrackStatus::WriteOrReadError,
+        };
+
+        let value: Value =
+            serde_json::from_str(&format_json(&tracks_data, None, None, None)).unwrap();
+
+        assert_eq!(value["status"], "write_or_read_error");
+        assert!(value["tracks"][1].get("lrc").is_none());
+        assert_eq!(value["device"], Value::Null);
+        assert!(value.get("parsed").is_none());
+        assert_eq!(
+            value["tracks"][1],
+            json!({"track": 2, "present": true, "format": "raw", "text": null, "raw": "00ff1b"})
+        );
+    }
+
+    #[test]
+    fn test_format_json_lrc() {
+        let tracks_data = TracksData {
+            track1: track(b"%A?", DataFormat::Iso),
+            track2: track(b";1?", DataFormat::Iso),
+            track3: track(b"", DataFormat::Iso),
+            status: TrackStatus::Ok,
+        };
+        let lrc = [LrcStatus::Ok, LrcStatus::Mismatch, LrcStatus::Ok];
+
+        let value: Value =
+            serde_json::from_str(&format_json(&tracks_data, None, None, Some(&lrc))).unwrap();
+
+        assert_eq!(value["tracks"][0]["lrc"], "ok");
+        assert_eq!(value["tracks"][1]["lrc"], "mismatch");
+        assert_eq!(value["tracks"][2]["lrc"], "ok");
+    }
+}
@@ -1,11 +1,22 @@
+pub mod json;
+
+use crate::combined_format;
+use crate::lrc_status::LrcStatus;
 use crate::msrx_tool_error::MsrxToolError;
+use crate::parse::ParsedCard;
+use crate::redaction::Redaction;
 use crate::tracks_data::TracksData;
+use json::{format_json, DeviceInfo};
+use serde::Deserialize;
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
+    /// JSON document with tracks, status and device information
     Json,
-    /// This format combines all tracks into one string, tracks separated by underscore
+    /// This format combines all tracks into one string, tracks separated by underscore.
+    /// Separator and backslash inside tracks are escaped with backslash
     Combined,
 }
 
@@ -21,17 +32,27 @@ impl FromStr for OutputFormat {
     }
 }
 
-pub fn format(tracks_data: &TracksData, format: &OutputFormat, separator: &Option<char>) -> String {
+pub fn format(
+    tracks_data: &TracksData,
+    format: &OutputFormat,
+    separator: &Option<char>,
+    device: Option<&DeviceInfo>,
+    parsed: Option<&ParsedCard>,
+    lrc: Option<&[LrcStatus; 3]>,
+    redaction: &Redaction,
+) -> String {
+    let tracks_data = &redaction.redact_tracks(tracks_data, parsed.map(ParsedCard::format));
+    let parsed = parsed.map(|parsed| redaction.redact_parsed(parsed));
+    let parsed = parsed.as_ref();
     match format {
-        OutputFormat::Json => format_json(tracks_data),
-        OutputFormat::Combined => format_combined(tracks_data, separator),
+        OutputFormat::Json => format_json(tracks_data, device, parsed, lrc),
+        OutputFormat::Combined => match parsed {
+            Some(parsed) => format!("{}\n{}", format_combined(tracks_data, separator), parsed),
+            None => format_combined(tracks_data, separator),
+        },
     }
 }
 
-fn format_json(_tracks_data: &TracksData) -> String {
-    todo!("Implement JSON output")
-}
-
 fn format_combined(tracks_data: &TracksData, separator: &Option<char>) -> String {
     let separator = separator.unwrap_or('_');
     let strings: Vec<String> = vec![
@@ -40,5 +61,198 @@ fn format_combined(tracks_data: &TracksData, separator: &Option<char>) -> String
         tracks_data.track3.to_string().unwrap(),
     ];
 
-    strings.join(&separator.to_string())
+    combined_format::join(&strings, separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_format::DataFormat;
+    use crate::parse::ParseFormat;
+    use crate::track_action::TrackAction;
+    use crate::track_data::TrackData;
+    use crate::track_status::TrackStatus;
+    use serde_json::Value;
+
+    const AAMVA_TRACK1: &str = "%CAMOUNTAIN VIEW^DOE$JOHN^1 MAIN ST^?";
+    const AAMVA_TRACK2: &str = ";636014123456789=271219900115=?";
+    const AAMVA_TRACK3: &str = concat!(
+        "%10",
+        "94043      ",
+        "C ",
+        "          ",
+        "    ",
+        "M510180BRNBLU",
+        "1234567890?"
+    );
+    const REDACTED_AAMVA_TRACK1: &str = "%CAMOUNTAIN VIEW^***$****^*********^?";
+    const REDACTED_AAMVA_TRACK2: &str = ";636014*****6789=271219900115=?";
+    const REDACTED_AAMVA_TRACK3: &str = concat!(
+        "%10",
+        "***********",
+        "C ",
+        "          ",
+        "    ",
+        "M510180BRNBLU",
+        "**********?"
+    );
+    const ISO4909_TRACK3: &str =
+        ";014111111111111111=84097825000125061233030000000001122=1=12345678==000000042?";
+    const REDACTED_ISO4909_TRACK3: &str =
+        ";01411111******1111=84097825000125061233030000000001122=1=********==000000042?";
+
+    fn hide_all() -> Redaction {
+        Redaction {
+            mask_pan: true,
+            hide_name: true,
+            hide_discretionary_data: true,
+        }
+    }
+
+    fn track(data: &str) -> TrackData {
+        TrackData {
+            data: data.as_bytes().to_vec(),
+            format: DataFormat::Iso,
+            action: TrackAction::for_data(data.as_bytes()),
+        }
+    }
+
+    /// Tracks are given as read, AAMVA track 3 uses track 1 characters
+    fn format_parsed(
+        tracks: [&str; 3],
+        parse_format: ParseFormat,
+        output_format: &OutputFormat,
+        redaction: &Redaction,
+    ) -> String {
+        let tracks_data = TracksData {
+            track1: track(tracks[0]),
+            track2: track(tracks[1]),
+            track3: track(tracks[2]),
+            status: TrackStatus::Ok,
+        };
+        let parsed = ParsedCard::parse(&tracks_data, parse_format);
+        format(
+            &tracks_data,
+            output_format,
+            &None,
+            None,
+            Some(&parsed),
+            None,
+            redaction,
+        )
+    }
+
+    const AAMVA_CARD: [&str; 3] = [AAMVA_TRACK1, AAMVA_TRACK2, AAMVA_TRACK3];
+
+    #[test]
+    fn test_format_json_redacts_aamva_track_text() {
+        let output = format_parsed(
+            AAMVA_CARD,
+            ParseFormat::Aamva,
+            &OutputFormat::Json,
+            &hide_all(),
+        );
+        let value: Value = serde_json::from_str(&output).unwrap();
+
+        for (index, track) in [
+            REDACTED_AAMVA_TRACK1,
+            REDACTED_AAMVA_TRACK2,
+            REDACTED_AAMVA_TRACK3,
+        ]
+        .iter()
+        .enumerate()
+        {
+            assert_eq!(value["tracks"][index]["text"], *track);
+            assert_eq!(value["tracks"][index]["raw"], hex::encode(track));
+        }
+        assert_eq!(value["parsed"]["track1"]["family_name"], "***");
+        assert_eq!(value["parsed"]["track3"]["postal_code"], "*****");
+        assert_eq!(value["parsed"]["track3"]["id"], "**********");
+        for clear in ["JOHN", "94043", "1234567890"] {
+            assert!(!output.contains(clear));
+            assert!(!output.contains(&hex::encode(clear)));
+        }
+    }
+
+    #[test]
+    fn test_format_combined_redacts_aamva_track_text() {
+        let output = format_parsed(
+            AAMVA_CARD,
+            ParseFormat::Aamva,
+            &OutputFormat::Combined,
+            &hide_all(),
+        );
+
+        assert_eq!(
+            output.lines().next(),
+            Some(
+                &*[
+                    REDACTED_AAMVA_TRACK1,
+                    REDACTED_AAMVA_TRACK2,
+                    REDACTED_AAMVA_TRACK3
+                ]
+                .join("_")
+            )
+        );
+        for clear in ["DOE", "MAIN ST", "94043", "1234567890"] {
+            assert!(!output.contains(clear));
+        }
+    }
+
+    #[test]
+    fn test_format_keeps_aamva_name_by_default() {
+        let output = format_parsed(
+            AAMVA_CARD,
+            ParseFormat::Aamva,
+            &OutputFormat::Combined,
+            &Redaction::default(),
+        );
+
+        assert_eq!(
+            output.lines().next(),
+            Some(
+                &*[
+                    AAMVA_TRACK1,
+                    REDACTED_AAMVA_TRACK2,
+                    &AAMVA_TRACK3.replace("1234567890", "**********")
+                ]
+                .join("_")
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_combined_redacts_iso4909_track3_text() {
+        let output = format_parsed(
+            ["", "", ISO4909_TRACK3],
+            ParseFormat::Iso4909,
+            &OutputFormat::Combined,
+            &Redaction::default(),
+        );
+
+        assert_eq!(
+            output.lines().next(),
+            Some(&*format!("__{}", REDACTED_ISO4909_TRACK3))
+        );
+        assert!(output.contains("SAN-1 ********"));
+        assert!(!output.contains("12345678"));
+    }
+
+    #[test]
+    fn test_format_json_redacts_iso4909_track3_text() {
+        let output = format_parsed(
+            ["", "", ISO4909_TRACK3],
+            ParseFormat::Iso4909,
+            &OutputFormat::Json,
+            &Redaction::default(),
+        );
+        let value: Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(value["tracks"][2]["text"], REDACTED_ISO4909_TRACK3);
+        assert_eq!(
+            value["tracks"][2]["raw"],
+            hex::encode(REDACTED_ISO4909_TRACK3)
+        );
+        assert_eq!(value["parsed"]["track3"]["san1"], "********");
+    }
 }
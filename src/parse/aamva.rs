@@ -0,0 +1,561 @@
+//! AAMVA driver license and ID card magnetic stripe:
+//!
+//! - Track 1: `%<state 2><city>^<family name>$<given names>^<address>^?`. City and name end at
+//!   `^` unless they use their whole length, 13 and 35 characters
+//! - Track 2: `;<IIN 6><ID number>=<expiry YYMM><birth date CCYYMMDD><ID number overflow>?`
+//! - Track 3: `%<CDS version><jurisdiction version><fixed length fields>?`
+//!
+//! Track 3 uses the same character set as track 1, so it must be read with 7 bits per character,
+//! e.g. raw data with `--decode-bpc 7,5,7`.
+
+use super::ParsedTrack;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::tracks_data::TracksData;
+use serde::Serialize;
+use std::fmt;
+use std::ops::Range;
+
+const TRACK1_START_SENTINEL: char = '%';
+const TRACK2_START_SENTINEL: char = ';';
+const TRACK3_START_SENTINEL: char = '%';
+pub const TRACK1_FIELD_SEPARATOR: char = '^';
+const TRACK2_FIELD_SEPARATOR: char = '=';
+pub const NAME_SEPARATOR: char = '$';
+const STATE_LENGTH: usize = 2;
+const CITY_MAX_LENGTH: usize = 13;
+const NAME_MAX_LENGTH: usize = 35;
+const IIN_LENGTH: usize = 6;
+const ID_NUMBER_MAX_LENGTH: usize = 13;
+const ID_NUMBER_OVERFLOW_MAX_LENGTH: usize = 5;
+const EXPIRY_LENGTH: usize = 4;
+const BIRTH_DATE_LENGTH: usize = 8;
+/// Expiry months which are not calendar months: 77 non-expiring, 88 expires on birthday of the
+/// expiry year, 99 non-expiring
+const SPECIAL_EXPIRY_MONTHS: [&str; 3] = ["77", "88", "99"];
+/// Lengths of track 3 fields up to eye color, the fields after it are optional
+const TRACK3_FIELD_LENGTHS: [usize; 11] = [1, 1, 11, 2, 10, 4, 1, 3, 3, 3, 3];
+const TRACK3_POSTAL_CODE_FIELD: usize = 2;
+const TRACK3_ID_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AamvaCard {
+    /// CDS version from track 3, `None` when track 3 is not available
+    pub version: Option<u8>,
+    pub track1: Option<ParsedTrack<Track1>>,
+    pub track2: Option<ParsedTrack<Track2>>,
+    pub track3: Option<ParsedTrack<Track3>>,
+}
+
+impl AamvaCard {
+    pub fn parse(tracks_data: &TracksData) -> AamvaCard {
+        let track3 =
+            ParsedTrack::parse(&tracks_data.track3, 3, TRACK3_START_SENTINEL, Track3::parse);
+        AamvaCard {
+            version: match &track3 {
+                Some(ParsedTrack::Parsed(track3)) => Some(track3.cds_version),
+                _ => None,
+            },
+            track1: ParsedTrack::parse(
+                &tracks_data.track1,
+                1,
+                TRACK1_START_SENTINEL,
+                Track1::parse,
+            ),
+            track2: ParsedTrack::parse(
+                &tracks_data.track2,
+                2,
+                TRACK2_START_SENTINEL,
+                Track2::parse,
+            ),
+            track3,
+        }
+    }
+}
+
+impl fmt::Display for AamvaCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            Some(version) => writeln!(f, "AAMVA version {}", version)?,
+            None => writeln!(f, "AAMVA version unknown")?,
+        }
+        ParsedTrack::fmt_track(&self.track1, 1, f)?;
+        writeln!(f)?;
+        ParsedTrack::fmt_track(&self.track2, 2, f)?;
+        writeln!(f)?;
+        ParsedTrack::fmt_track(&self.track3, 3, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track1 {
+    pub state: String,
+    pub city: String,
+    pub family_name: String,
+    pub given_names: Vec<String>,
+    /// Address lines
+    pub address: Vec<String>,
+}
+
+impl Track1 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track1, MsrxToolError> {
+        check_ascii(1, content)?;
+        if content.len() < STATE_LENGTH
+            || !content[..STATE_LENGTH]
+                .chars()
+                .all(|c| c.is_ascii_uppercase())
+        {
+            return Err(invalid(1, "state must be two letters"));
+        }
+        let (state, rest) = content.split_at(STATE_LENGTH);
+        let (city, rest) = split_field(rest, CITY_MAX_LENGTH);
+        let (name, rest) = split_field(rest, NAME_MAX_LENGTH);
+        let address = rest
+            .split(TRACK1_FIELD_SEPARATOR)
+            .next()
+            .unwrap_or_default();
+
+        let mut names = name.split(NAME_SEPARATOR).map(str::trim);
+        let family_name = names.next().unwrap_or_default().to_string();
+        if family_name.is_empty() {
+            return Err(invalid(1, "family name is missing"));
+        }
+
+        Ok(Track1 {
+            state: state.to_string(),
+            city: city.trim().to_string(),
+            family_name,
+            given_names: non_empty(names),
+            address: non_empty(address.split(NAME_SEPARATOR).map(str::trim)),
+        })
+    }
+
+    /// Position in track content where name and address start, they continue to the end
+    pub fn holder_start(content: &str) -> Result<usize, MsrxToolError> {
+        Track1::parse(content)?;
+        let (_, rest) = split_field(&content[STATE_LENGTH..], CITY_MAX_LENGTH);
+        Ok(content.len() - rest.len())
+    }
+}
+
+impl fmt::Display for Track1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "state {}, city {}, name {} {}, address {}",
+            self.state,
+            self.city,
+            self.given_names.join(" "),
+            self.family_name,
+            self.address.join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track2 {
+    /// Issuer identification number of the jurisdiction
+    pub iin: String,
+    /// License or ID number including overflow digits
+    pub id_number: String,
+    /// YYMM, month 77 or 99 means non-expiring and 88 expiring on the birthday
+    pub expiry: String,
+    /// CCYYMMDD
+    pub birth_date: String,
+}
+
+impl Track2 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track2, MsrxToolError> {
+        check_ascii(2, content)?;
+        let (number, rest) = content
+            .split_once(TRACK2_FIELD_SEPARATOR)
+            .ok_or_else(|| invalid(2, "field separator = is missing"))?;
+        if !is_digits(number)
+            || number.len() <= IIN_LENGTH
+            || number.len() > IIN_LENGTH + ID_NUMBER_MAX_LENGTH
+        {
+            return Err(invalid(
+                2,
+                &format!(
+                    "IIN and ID number must be {} digits and 1-{} digits",
+                    IIN_LENGTH, ID_NUMBER_MAX_LENGTH
+                ),
+            ));
+        }
+        let dates_length = EXPIRY_LENGTH + BIRTH_DATE_LENGTH;
+        if rest.len() < dates_length || !is_digits(&rest[..dates_length]) {
+            return Err(invalid(
+                2,
+                "expiry date and birth date must be 4 and 8 digits",
+            ));
+        }
+        let (expiry, rest) = rest.split_at(EXPIRY_LENGTH);
+        let (birth_date, overflow) = rest.split_at(BIRTH_DATE_LENGTH);
+        let overflow = overflow.trim_end_matches(TRACK2_FIELD_SEPARATOR);
+        if !is_digits(overflow) || overflow.len() > ID_NUMBER_OVERFLOW_MAX_LENGTH {
+            return Err(invalid(
+                2,
+                &format!(
+                    "ID number overflow must be up to {} digits",
+                    ID_NUMBER_OVERFLOW_MAX_LENGTH
+                ),
+            ));
+        }
+        let expiry_month = &expiry[2..];
+        if !is_month(expiry_month) && !SPECIAL_EXPIRY_MONTHS.contains(&expiry_month) {
+            return Err(invalid(
+                2,
+                &format!("invalid expiry month {}", expiry_month),
+            ));
+        }
+        let (birth_month, birth_day) = (&birth_date[4..6], &birth_date[6..]);
+        if !is_month(birth_month) || !matches!(birth_day.parse::<u8>(), Ok(1..=31)) {
+            return Err(invalid(2, &format!("invalid birth date {}", birth_date)));
+        }
+
+        Ok(Track2 {
+            iin: number[..IIN_LENGTH].to_string(),
+            id_number: format!("{}{}", &number[IIN_LENGTH..], overflow),
+            expiry: expiry.to_string(),
+            birth_date: birth_date.to_string(),
+        })
+    }
+
+    /// Positions of ID number and its overflow after birth date in track content, IIN is
+    /// before the ID number
+    pub fn id_ranges(content: &str) -> Result<[Range<usize>; 2], MsrxToolError> {
+        let track = Track2::parse(content)?;
+        let id_end = content
+            .find(TRACK2_FIELD_SEPARATOR)
+            .unwrap_or(content.len());
+        let overflow_start = id_end + 1 + EXPIRY_LENGTH + BIRTH_DATE_LENGTH;
+        let overflow_end = overflow_start + track.id_number.len() - (id_end - IIN_LENGTH);
+        Ok([IIN_LENGTH..id_end, overflow_start..overflow_end])
+    }
+}
+
+impl fmt::Display for Track2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "IIN {}, ID number {}, expiry {}, birth date {}",
+            self.iin, self.id_number, self.expiry, self.birth_date
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track3 {
+    pub cds_version: u8,
+    pub jurisdiction_version: u8,
+    pub postal_code: String,
+    pub class: String,
+    pub restrictions: String,
+    pub endorsements: String,
+    /// M or F
+    pub sex: String,
+    /// Feet and inches, e.g. 510, or centimeters
+    pub height: String,
+    pub weight: String,
+    pub hair_color: String,
+    pub eye_color: String,
+    /// Optional field for the jurisdiction
+    pub id: String,
+}
+
+impl Track3 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track3, MsrxToolError> {
+        check_ascii(3, content)?;
+        let minimum_length: usize = TRACK3_FIELD_LENGTHS.iter().sum();
+        if content.len() < minimum_length {
+            return Err(invalid(
+                3,
+                &format!("track must have at least {} characters", minimum_length),
+            ));
+        }
+        let mut rest = content;
+        let fields = TRACK3_FIELD_LENGTHS.map(|length| {
+            let (field, remaining) = rest.split_at(length);
+            rest = remaining;
+            field
+        });
+        let version = |field: &str, name: &str| {
+            field
+                .parse::<u8>()
+                .map_err(|_| invalid(3, &format!("{} must be a digit", name)))
+        };
+        let sex = match fields[6] {
+            "1" | "M" => "M",
+            "2" | "F" => "F",
+            sex => return Err(invalid(3, &format!("invalid sex {}", sex))),
+        };
+        let id_length = rest.len().min(TRACK3_ID_LENGTH);
+
+        Ok(Track3 {
+            cds_version: version(fields[0], "CDS version")?,
+            jurisdiction_version: version(fields[1], "jurisdiction version")?,
+            postal_code: fields[2].trim().to_string(),
+            class: fields[3].trim().to_string(),
+            restrictions: fields[4].trim().to_string(),
+            endorsements: fields[5].trim().to_string(),
+            sex: sex.to_string(),
+            height: fields[7].trim().to_string(),
+            weight: fields[8].trim().to_string(),
+            hair_color: fields[9].trim().to_string(),
+            eye_color: fields[10].trim().to_string(),
+            id: rest[..id_length].trim().to_string(),
+        })
+    }
+
+    /// Positions of postal code and ID in track content, both keep their padding
+    pub fn holder_ranges(content: &str) -> Result<[Range<usize>; 2], MsrxToolError> {
+        Track3::parse(content)?;
+        let postal_code_start: usize = TRACK3_FIELD_LENGTHS[..TRACK3_POSTAL_CODE_FIELD]
+            .iter()
+            .sum();
+        let postal_code_end = postal_code_start + TRACK3_FIELD_LENGTHS[TRACK3_POSTAL_CODE_FIELD];
+        let id_start: usize = TRACK3_FIELD_LENGTHS.iter().sum();
+        let id_end = content.len().min(id_start + TRACK3_ID_LENGTH);
+        Ok([postal_code_start..postal_code_end, id_start..id_end])
+    }
+}
+
+impl fmt::Display for Track3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "postal code {}, class {}, restrictions {}, endorsements {}, sex {}, height {}, weight {}, hair {}, eyes {}",
+            self.postal_code,
+            self.class,
+            self.restrictions,
+            self.endorsements,
+            self.sex,
+            self.height,
+            self.weight,
+            self.hair_color,
+            self.eye_color
+        )
+    }
+}
+
+fn invalid(track_number: usize, reason: &str) -> MsrxToolError {
+    MsrxToolError::InvalidCardData(track_number, reason.to_string())
+}
+
+/// Track characters are ASCII, which makes fields safe to split by byte position
+fn check_ascii(track_number: usize, content: &str) -> Result<(), MsrxToolError> {
+    if content.is_ascii() {
+        Ok(())
+    } else {
+        Err(invalid(
+            track_number,
+            "track must have only ASCII characters",
+        ))
+    }
+}
+
+/// Splits a field which ends at the field separator, or after `max_length` characters when it
+/// uses the whole length
+fn split_field(text: &str, max_length: usize) -> (&str, &str) {
+    match text.find(TRACK1_FIELD_SEPARATOR) {
+        Some(end) if end <= max_length => (&text[..end], &text[end + 1..]),
+        _ => text.split_at(text.len().min(max_length)),
+    }
+}
+
+fn non_empty<'a>(parts: impl Iterator<Item = &'a str>) -> Vec<String> {
+    parts
+        .filter(|part| !part.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_digits(text: &str) -> bool {
+    text.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_month(month: &str) -> bool {
+    matches!(month.parse::<u8>(), Ok(1..=12))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_format::DataFormat;
+    use crate::parse::{ParseFormat, ParsedCard};
+    use crate::track_action::TrackAction;
+    use crate::track_data::TrackData;
+    use crate::track_status::TrackStatus;
+
+    const TRACK1: &str = "%CAMOUNTAIN VIEW^DOE$JOHN$Q^1600 AMPHITHEATRE PKWY$APT 1^?";
+    const TRACK2: &str = ";636014123456789=271219900115=?";
+    const TRACK3_CONTENT: &str = concat!(
+        "10",
+        "94043      ",
+        "C ",
+        "          ",
+        "    ",
+        "M",
+        "510",
+        "180",
+        "BRN",
+        "BLU",
+        "1234567890"
+    );
+
+    fn track(data: &str) -> TrackData {
+        TrackData {
+            data: data.as_bytes().to_vec(),
+            format: DataFormat::Iso,
+            action: TrackAction::for_data(data.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_parse_track1() -> Result<(), MsrxToolError> {
+        assert_eq!(
+            Track1::parse(&TRACK1[1..TRACK1.len() - 1])?,
+            Track1 {
+                state: "CA".to_string(),
+                city: "MOUNTAIN VIEW".to_string(),
+                family_name: "DOE".to_string(),
+                given_names: vec!["JOHN".to_string(), "Q".to_string()],
+                address: vec!["1600 AMPHITHEATRE PKWY".to_string(), "APT 1".to_string()],
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_track1_full_length_city() -> Result<(), MsrxToolError> {
+        let track1 = Track1::parse("TXSAN ANTONIO XSMITH$JANE^1 MAIN ST^")?;
+
+        assert_eq!(track1.city, "SAN ANTONIO X");
+        assert_eq!(track1.family_name, "SMITH");
+        assert_eq!(track1.address, vec!["1 MAIN ST".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_track2() -> Result<(), MsrxToolError> {
+        assert_eq!(
+            Track2::parse(&TRACK2[1..TRACK2.len() - 1])?,
+            Track2 {
+                iin: "636014".to_string(),
+                id_number: "123456789".to_string(),
+                expiry: "2712".to_string(),
+                birth_date: "19900115".to_string(),
+            }
+        );
+        let overflow = Track2::parse("6360141234567890123=7799199001011234")?;
+        assert_eq!(overflow.id_number, "12345678901231234");
+        let [id, id_overflow] = Track2::id_ranges("6360141234567890123=7799199001011234")?;
+        assert_eq!((id, id_overflow), (6..19, 32..36));
+        assert_eq!(overflow.expiry, "7799");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_track3() -> Result<(), MsrxToolError> {
+        let track3 = Track3::parse(TRACK3_CONTENT)?;
+
+        assert_eq!(track3.cds_version, 1);
+        assert_eq!(track3.jurisdiction_version, 0);
+        assert_eq!(track3.postal_code, "94043");
+        assert_eq!(track3.class, "C");
+        assert_eq!(track3.sex, "M");
+        assert_eq!(track3.height, "510");
+        assert_eq!(track3.weight, "180");
+        assert_eq!(track3.hair_color, "BRN");
+        assert_eq!(track3.eye_color, "BLU");
+        assert_eq!(track3.id, "1234567890");
+        let [postal_code, id] = Track3::holder_ranges(TRACK3_CONTENT)?;
+        assert_eq!(&TRACK3_CONTENT[postal_code], "94043      ");
+        assert_eq!(&TRACK3_CONTENT[id], "1234567890");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for content in ["C", "1AB^C^D^", "CACITY^^ADDRESS^", "Cé^CITY^"] {
+            assert!(
+                matches!(
+                    Track1::parse(content),
+                    Err(MsrxToolError::InvalidCardData(1, _))
+                ),
+                "{}",
+                content
+            );
+        }
+        for content in [
+            "636014123456789",
+            "636014=271219900115",
+            "63601412345678901234=271219900115",
+            "636014123=2712199001",
+            "636014123=271319900115",
+            "636014123=271219901315",
+            "636014123=271219900100",
+            "636014123=2712199001151234567",
+            "636014123=27121990011é",
+        ] {
+            assert!(
+                matches!(
+                    Track2::parse(content),
+                    Err(MsrxToolError::InvalidCardData(2, _))
+                ),
+                "{}",
+                content
+            );
+        }
+        for content in [
+            "10",
+            "X094043      C                M510180BRNBLU",
+            "1094043      C                X510180BRNBLU",
+            "1094043      C                M510180BRNBLé",
+        ] {
+            assert!(
+                matches!(
+                    Track3::parse(content),
+                    Err(MsrxToolError::InvalidCardData(3, _))
+                ),
+                "{}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_card_detects_version() {
+        let tracks_data = TracksData {
+            track1: track(TRACK1),
+            track2: track(TRACK2),
+            track3: track(&format!("%!!{}?", &TRACK3_CONTENT[2..])),
+            status: TrackStatus::Ok,
+        };
+
+        let card = AamvaCard::parse(&tracks_data);
+
+        assert!(matches!(card.track1, Some(ParsedTrack::Parsed(_))));
+        assert!(matches!(card.track2, Some(ParsedTrack::Parsed(_))));
+        // CDS version must be a digit
+        assert!(matches!(card.track3, Some(ParsedTrack::Error { .. })));
+        assert_eq!(card.version, None);
+
+        let tracks_data = TracksData {
+            track3: track(&format!("%21{}?", TRACK3_CONTENT[2..].replace('M', "F"))),
+            ..tracks_data
+        };
+        let card = ParsedCard::parse(&tracks_data, ParseFormat::Aamva);
+        let value = serde_json::to_value(&card).unwrap();
+
+        assert_eq!(value["format"], "aamva");
+        assert_eq!(value["version"], 2);
+        assert_eq!(value["track3"]["sex"], "F");
+        assert_eq!(value["track2"]["birth_date"], "19900115");
+        assert!(card.to_string().starts_with(
+            "AAMVA version 2\nTrack 1: state CA, city MOUNTAIN VIEW, name JOHN Q DOE"
+        ));
+    }
+}
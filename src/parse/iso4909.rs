@@ -0,0 +1,573 @@
+//! ISO 4909 financial transaction track 3:
+//!
+//! `;<format code><PAN>=<country code><currency><amounts and cycle data>...<discretionary data>?`
+//!
+//! Optional fixed length fields (country code, expiry date and card security number) are
+//! replaced with a single `=` when not given, SAN-1 and SAN-2 always end with `=`. Track 3 can
+//! also be built from these fields for writing.
+
+use super::ParsedTrack;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::tracks_data::TracksData;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const TRACK3_START_SENTINEL: char = ';';
+const TRACK3_END_SENTINEL: char = '?';
+const FIELD_SEPARATOR: char = '=';
+const FORMAT_CODE_LENGTH: usize = 2;
+const MAX_PAN_LENGTH: usize = 19;
+const COUNTRY_CODE_LENGTH: usize = 3;
+const CURRENCY_CODE_LENGTH: usize = 3;
+const AMOUNT_LENGTH: usize = 4;
+const CYCLE_BEGIN_LENGTH: usize = 4;
+const CYCLE_LENGTH_LENGTH: usize = 2;
+const PIN_CONTROL_PARAMETERS_LENGTH: usize = 6;
+const SERVICE_RESTRICTION_LENGTH: usize = 2;
+const EXPIRY_LENGTH: usize = 4;
+const CARD_SECURITY_NUMBER_LENGTH: usize = 9;
+const MAX_SAN_LENGTH: usize = 12;
+const CRYPTO_CHECK_DIGITS_LENGTH: usize = 6;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Iso4909Card {
+    pub track3: Option<ParsedTrack<Track3>>,
+}
+
+impl Iso4909Card {
+    /// Parses track 3, tracks 1 and 2 follow ISO 7813
+    pub fn parse(tracks_data: &TracksData) -> Iso4909Card {
+        Iso4909Card {
+            track3: ParsedTrack::parse(
+                &tracks_data.track3,
+                3,
+                TRACK3_START_SENTINEL,
+                Track3::parse,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Iso4909Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        ParsedTrack::fmt_track(&self.track3, 3, f)
+    }
+}
+
+/// Fields of track 3. JSON output of `read --parse iso4909 --show-clear-data` can be given to
+/// `build-track3`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Track3 {
+    pub format_code: String,
+    pub pan: String,
+    pub country_code: Option<String>,
+    pub currency_code: String,
+    pub currency_exponent: u8,
+    /// Amount authorized per cycle
+    pub amount_authorized: u16,
+    /// Amount remaining in the current cycle
+    pub amount_remaining: u16,
+    /// YDDD, last digit of year and day of year
+    pub cycle_begin: String,
+    /// Days
+    pub cycle_length: u8,
+    /// PIN tries left
+    pub retry_count: u8,
+    pub pin_control_parameters: String,
+    pub interchange_control: String,
+    pub pan_service_restriction: String,
+    pub san1_service_restriction: String,
+    pub san2_service_restriction: String,
+    /// YYMM
+    pub expiry: Option<String>,
+    pub card_sequence_number: String,
+    pub card_security_number: Option<String>,
+    /// Subsidiary account number 1
+    pub san1: Option<String>,
+    /// Subsidiary account number 2
+    pub san2: Option<String>,
+    pub transaction_relay_indicator: String,
+    pub crypto_check_digits: String,
+    pub discretionary_data: String,
+}
+
+impl Track3 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track3, MsrxToolError> {
+        let mut fields = Fields { rest: content };
+        let track3 = Track3 {
+            format_code: fields.fixed("format code", FORMAT_CODE_LENGTH)?,
+            pan: fields.variable("PAN", 1, MAX_PAN_LENGTH)?,
+            country_code: fields.optional("country code", COUNTRY_CODE_LENGTH)?,
+            currency_code: fields.fixed("currency code", CURRENCY_CODE_LENGTH)?,
+            currency_exponent: fields.number("currency exponent", 1)?,
+            amount_authorized: fields.number("amount authorized", AMOUNT_LENGTH)?,
+            amount_remaining: fields.number("amount remaining", AMOUNT_LENGTH)?,
+            cycle_begin: fields.fixed("cycle begin", CYCLE_BEGIN_LENGTH)?,
+            cycle_length: fields.number("cycle length", CYCLE_LENGTH_LENGTH)?,
+            retry_count: fields.number("retry count", 1)?,
+            pin_control_parameters: fields
+                .fixed("PIN control parameters", PIN_CONTROL_PARAMETERS_LENGTH)?,
+            interchange_control: fields.fixed("interchange control", 1)?,
+            pan_service_restriction: fields
+                .fixed("PAN service restriction", SERVICE_RESTRICTION_LENGTH)?,
+            san1_service_restriction: fields
+                .fixed("SAN-1 service restriction", SERVICE_RESTRICTION_LENGTH)?,
+            san2_service_restriction: fields
+                .fixed("SAN-2 service restriction", SERVICE_RESTRICTION_LENGTH)?,
+            expiry: fields.optional("expiry date", EXPIRY_LENGTH)?,
+            card_sequence_number: fields.fixed("card sequence number", 1)?,
+            card_security_number: fields
+                .optional("card security number", CARD_SECURITY_NUMBER_LENGTH)?,
+            san1: Some(fields.variable("SAN-1", 0, MAX_SAN_LENGTH)?).filter(|san| !san.is_empty()),
+            san2: Some(fields.variable("SAN-2", 0, MAX_SAN_LENGTH)?).filter(|san| !san.is_empty()),
+            transaction_relay_indicator: fields.fixed("transaction relay indicator", 1)?,
+            crypto_check_digits: fields.fixed("crypto check digits", CRYPTO_CHECK_DIGITS_LENGTH)?,
+            discretionary_data: fields.rest.to_string(),
+        };
+        if !is_digits(&track3.discretionary_data) {
+            return Err(invalid("discretionary data must be digits"));
+        }
+        Ok(track3)
+    }
+
+    /// Joins fields into track content without sentinels. Fields are not validated, so redacted
+    /// fields can be shown in place.
+    pub fn content(&self) -> String {
+        let optional =
+            |field: &Option<String>| field.clone().unwrap_or_else(|| FIELD_SEPARATOR.to_string());
+        let variable = |field: &str| format!("{}{}", field, FIELD_SEPARATOR);
+        [
+            self.format_code.clone(),
+            variable(&self.pan),
+            optional(&self.country_code),
+            self.currency_code.clone(),
+            self.currency_exponent.to_string(),
+            format!(
+                "{:0length$}",
+                self.amount_authorized,
+                length = AMOUNT_LENGTH
+            ),
+            format!("{:0length$}", self.amount_remaining, length = AMOUNT_LENGTH),
+            self.cycle_begin.clone(),
+            format!(
+                "{:0length$}",
+                self.cycle_length,
+                length = CYCLE_LENGTH_LENGTH
+            ),
+            self.retry_count.to_string(),
+            self.pin_control_parameters.clone(),
+            self.interchange_control.clone(),
+            self.pan_service_restriction.clone(),
+            self.san1_service_restriction.clone(),
+            self.san2_service_restriction.clone(),
+            optional(&self.expiry),
+            self.card_sequence_number.clone(),
+            optional(&self.card_security_number),
+            variable(self.san1.as_deref().unwrap_or_default()),
+            variable(self.san2.as_deref().unwrap_or_default()),
+            self.transaction_relay_indicator.clone(),
+            self.crypto_check_digits.clone(),
+            self.discretionary_data.clone(),
+        ]
+        .concat()
+    }
+
+    /// Builds track 3 with sentinels. Fields are validated like they are parsed, and the track
+    /// like track data given to the write command.
+    pub fn to_track(&self) -> Result<String, MsrxToolError> {
+        let numbers = [
+            ("currency exponent", u16::from(self.currency_exponent), 1),
+            ("amount authorized", self.amount_authorized, AMOUNT_LENGTH),
+            ("amount remaining", self.amount_remaining, AMOUNT_LENGTH),
+            (
+                "cycle length",
+                u16::from(self.cycle_length),
+                CYCLE_LENGTH_LENGTH,
+            ),
+            ("retry count", u16::from(self.retry_count), 1),
+        ]
+        .map(|(name, number, length)| {
+            let digits = format!("{:0length$}", number, length = length);
+            if digits.len() > length {
+                return Err(invalid(&format!("{} must be {} digits", name, length)));
+            }
+            Ok(digits)
+        });
+        let [currency_exponent, amount_authorized, amount_remaining, cycle_length, retry_count] =
+            numbers;
+        let track = [
+            check_fixed("format code", &self.format_code, FORMAT_CODE_LENGTH)?,
+            check_variable("PAN", &self.pan, 1, MAX_PAN_LENGTH)?,
+            check_optional("country code", &self.country_code, COUNTRY_CODE_LENGTH)?,
+            check_fixed("currency code", &self.currency_code, CURRENCY_CODE_LENGTH)?,
+            currency_exponent?,
+            amount_authorized?,
+            amount_remaining?,
+            check_fixed("cycle begin", &self.cycle_begin, CYCLE_BEGIN_LENGTH)?,
+            cycle_length?,
+            retry_count?,
+            check_fixed(
+                "PIN control parameters",
+                &self.pin_control_parameters,
+                PIN_CONTROL_PARAMETERS_LENGTH,
+            )?,
+            check_fixed("interchange control", &self.interchange_control, 1)?,
+            check_fixed(
+                "PAN service restriction",
+                &self.pan_service_restriction,
+                SERVICE_RESTRICTION_LENGTH,
+            )?,
+            check_fixed(
+                "SAN-1 service restriction",
+                &self.san1_service_restriction,
+                SERVICE_RESTRICTION_LENGTH,
+            )?,
+            check_fixed(
+                "SAN-2 service restriction",
+                &self.san2_service_restriction,
+                SERVICE_RESTRICTION_LENGTH,
+            )?,
+            check_optional("expiry date", &self.expiry, EXPIRY_LENGTH)?,
+            check_fixed("card sequence number", &self.card_sequence_number, 1)?,
+            check_optional(
+                "card security number",
+                &self.card_security_number,
+                CARD_SECURITY_NUMBER_LENGTH,
+            )?,
+            check_variable(
+                "SAN-1",
+                self.san1.as_deref().unwrap_or_default(),
+                0,
+                MAX_SAN_LENGTH,
+            )?,
+            check_variable(
+                "SAN-2",
+                self.san2.as_deref().unwrap_or_default(),
+                0,
+                MAX_SAN_LENGTH,
+            )?,
+            check_fixed(
+                "transaction relay indicator",
+                &self.transaction_relay_indicator,
+                1,
+            )?,
+            check_fixed(
+                "crypto check digits",
+                &self.crypto_check_digits,
+                CRYPTO_CHECK_DIGITS_LENGTH,
+            )?,
+        ]
+        .concat();
+        if !is_digits(&self.discretionary_data) {
+            return Err(invalid("discretionary data must be digits"));
+        }
+        let track = format!(
+            "{}{}{}{}",
+            TRACK3_START_SENTINEL, track, self.discretionary_data, TRACK3_END_SENTINEL
+        );
+        TracksData::from_iso_tracks([None, None, Some(&track)])?;
+        Ok(track)
+    }
+}
+
+impl fmt::Display for Track3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let none = || "none".to_string();
+        write!(
+            f,
+            "format code {}, PAN {}, country {}, currency {} (exponent {}), amount authorized {}, \
+             remaining {}, cycle begin {}, cycle length {}, retry count {}, expiry {}, \
+             SAN-1 {}, SAN-2 {}, discretionary data {}",
+            self.format_code,
+            self.pan,
+            self.country_code.clone().unwrap_or_else(none),
+            self.currency_code,
+            self.currency_exponent,
+            self.amount_authorized,
+            self.amount_remaining,
+            self.cycle_begin,
+            self.cycle_length,
+            self.retry_count,
+            self.expiry.clone().unwrap_or_else(none),
+            self.san1.clone().unwrap_or_else(none),
+            self.san2.clone().unwrap_or_else(none),
+            self.discretionary_data
+        )
+    }
+}
+
+/// Reads fields from the start of track content
+struct Fields<'a> {
+    rest: &'a str,
+}
+
+impl Fields<'_> {
+    fn fixed(&mut self, name: &str, length: usize) -> Result<String, MsrxToolError> {
+        let field = self.rest.get(..length).filter(|field| is_digits(field));
+        match field {
+            Some(field) => {
+                self.rest = &self.rest[length..];
+                Ok(field.to_string())
+            }
+            None => Err(invalid(&format!("{} must be {} digits", name, length))),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(
+        &mut self,
+        name: &str,
+        length: usize,
+    ) -> Result<T, MsrxToolError> {
+        self.fixed(name, length)?
+            .parse()
+            .map_err(|_| invalid(&format!("{} must be {} digits", name, length)))
+    }
+
+    /// Fixed length field or a field separator when it is not given
+    fn optional(&mut self, name: &str, length: usize) -> Result<Option<String>, MsrxToolError> {
+        match self.rest.strip_prefix(FIELD_SEPARATOR) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(None)
+            }
+            None => self.fixed(name, length).map(Some),
+        }
+    }
+
+    /// Field which ends with a field separator
+    fn variable(
+        &mut self,
+        name: &str,
+        min_length: usize,
+        max_length: usize,
+    ) -> Result<String, MsrxToolError> {
+        let (field, rest) = self
+            .rest
+            .split_once(FIELD_SEPARATOR)
+            .ok_or_else(|| invalid(&format!("{} must be followed by {}", name, FIELD_SEPARATOR)))?;
+        check_variable(name, field, min_length, max_length)?;
+        self.rest = rest;
+        Ok(field.to_string())
+    }
+}
+
+fn check_fixed(name: &str, field: &str, length: usize) -> Result<String, MsrxToolError> {
+    if field.len() != length || !is_digits(field) {
+        return Err(invalid(&format!("{} must be {} digits", name, length)));
+    }
+    Ok(field.to_string())
+}
+
+fn check_optional(
+    name: &str,
+    field: &Option<String>,
+    length: usize,
+) -> Result<String, MsrxToolError> {
+    match field {
+        Some(field) => check_fixed(name, field, length),
+        None => Ok(FIELD_SEPARATOR.to_string()),
+    }
+}
+
+/// Variable length field with its field separator
+fn check_variable(
+    name: &str,
+    field: &str,
+    min_length: usize,
+    max_length: usize,
+) -> Result<String, MsrxToolError> {
+    if !(min_length..=max_length).contains(&field.len()) || !is_digits(field) {
+        return Err(invalid(&format!(
+            "{} must be {}-{} digits",
+            name, min_length, max_length
+        )));
+    }
+    Ok(format!("{}{}", field, FIELD_SEPARATOR))
+}
+
+fn invalid(reason: &str) -> MsrxToolError {
+    MsrxToolError::InvalidCardData(3, reason.to_string())
+}
+
+fn is_digits(text: &str) -> bool {
+    text.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{ParseFormat, ParsedCard};
+
+    const TRACK3: &str = concat!(
+        ";",
+        "01",
+        "4111111111111111=",
+        "840",
+        "978",
+        "2",
+        "5000",
+        "1250",
+        "6123",
+        "30",
+        "3",
+        "000000",
+        "0",
+        "00",
+        "11",
+        "22",
+        "=",
+        "1",
+        "=",
+        "12345678=",
+        "=",
+        "0",
+        "000000",
+        "42",
+        "?"
+    );
+
+    fn track3() -> Track3 {
+        Track3 {
+            format_code: "01".to_string(),
+            pan: "4111111111111111".to_string(),
+            country_code: Some("840".to_string()),
+            currency_code: "978".to_string(),
+            currency_exponent: 2,
+            amount_authorized: 5000,
+            amount_remaining: 1250,
+            cycle_begin: "6123".to_string(),
+            cycle_length: 30,
+            retry_count: 3,
+            pin_control_parameters: "000000".to_string(),
+            interchange_control: "0".to_string(),
+            pan_service_restriction: "00".to_string(),
+            san1_service_restriction: "11".to_string(),
+            san2_service_restriction: "22".to_string(),
+            expiry: None,
+            card_sequence_number: "1".to_string(),
+            card_security_number: None,
+            san1: Some("12345678".to_string()),
+            san2: None,
+            transaction_relay_indicator: "0".to_string(),
+            crypto_check_digits: "000000".to_string(),
+            discretionary_data: "42".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_track3() -> Result<(), MsrxToolError> {
+        assert_eq!(Track3::parse(&TRACK3[1..TRACK3.len() - 1])?, track3());
+        assert_eq!(track3().content(), &TRACK3[1..TRACK3.len() - 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_track3() -> Result<(), MsrxToolError> {
+        assert_eq!(track3().to_track()?, TRACK3);
+
+        let with_optional_fields = Track3 {
+            expiry: Some("2612".to_string()),
+            card_security_number: Some("123456789".to_string()),
+            san1: None,
+            ..track3()
+        };
+        let track = with_optional_fields.to_track()?;
+        assert_eq!(
+            Track3::parse(&track[1..track.len() - 1])?,
+            with_optional_fields
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_errors() {
+        for track3 in [
+            Track3 {
+                format_code: "1".to_string(),
+                ..track3()
+            },
+            Track3 {
+                pan: "4111x".to_string(),
+                ..track3()
+            },
+            Track3 {
+                pan: String::new(),
+                ..track3()
+            },
+            Track3 {
+                amount_authorized: 10000,
+                ..track3()
+            },
+            Track3 {
+                retry_count: 10,
+                ..track3()
+            },
+            Track3 {
+                country_code: Some("84".to_string()),
+                ..track3()
+            },
+            Track3 {
+                discretionary_data: "4=2".to_string(),
+                ..track3()
+            },
+        ] {
+            assert!(
+                matches!(track3.to_track(), Err(MsrxToolError::InvalidCardData(3, _))),
+                "{:?}",
+                track3
+            );
+        }
+
+        let too_long = Track3 {
+            discretionary_data: "0".repeat(40),
+            ..track3()
+        };
+        assert!(matches!(
+            too_long.to_track(),
+            Err(MsrxToolError::DataForTrackIsTooLong(3, _, 107))
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for content in [
+            "",
+            "014111111111111111",
+            "01=840",
+            "014111111111111111=84",
+            &TRACK3[1..60],
+            TRACK3[1..TRACK3.len() - 1]
+                .replace("=0000000", "=0")
+                .as_str(),
+        ] {
+            assert!(
+                matches!(
+                    Track3::parse(content),
+                    Err(MsrxToolError::InvalidCardData(3, _))
+                ),
+                "{}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_card_and_json_round_trip() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str(&format!("__{}", TRACK3), &'_')?;
+
+        let card = ParsedCard::parse(&tracks_data, ParseFormat::Iso4909);
+        let value = serde_json::to_value(&card).unwrap();
+
+        assert_eq!(value["format"], "iso4909");
+        assert_eq!(value["track3"]["amount_remaining"], 1250);
+        assert_eq!(value["track3"]["expiry"], serde_json::Value::Null);
+        let track3: Track3 = serde_json::from_value(value["track3"].clone()).unwrap();
+        assert_eq!(track3.to_track()?, TRACK3);
+        assert!(card.to_string().starts_with(
+            "Track 3: format code 01, PAN 4111111111111111, country 840, currency 978 (exponent 2)"
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+//! ISO/IEC 7813 payment card tracks:
+//!
+//! - Track 1 format B: `%B<PAN>^<NAME>^<YYMM><service code><discretionary data>?`
+//! - Track 2: `;<PAN>=<YYMM><service code><discretionary data>?`
+
+use super::ParsedTrack;
+use crate::check_digit;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::tracks_data::TracksData;
+use serde::Serialize;
+use std::fmt;
+
+const TRACK1_START_SENTINEL: char = '%';
+const TRACK2_START_SENTINEL: char = ';';
+const TRACK1_FIELD_SEPARATOR: char = '^';
+const TRACK2_FIELD_SEPARATOR: char = '=';
+const FORMAT_CODE_B: char = 'B';
+const MAX_PAN_LENGTH: usize = 19;
+const MIN_NAME_LENGTH: usize = 2;
+const MAX_NAME_LENGTH: usize = 26;
+const EXPIRY_LENGTH: usize = 4;
+const SERVICE_CODE_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Iso7813Card {
+    pub track1: Option<ParsedTrack<Track1>>,
+    pub track2: Option<ParsedTrack<Track2>>,
+}
+
+impl Iso7813Card {
+    /// Parses tracks 1 and 2, track 3 is not used by payment cards
+    pub fn parse(tracks_data: &TracksData) -> Iso7813Card {
+        Iso7813Card {
+            track1: ParsedTrack::parse(
+                &tracks_data.track1,
+                1,
+                TRACK1_START_SENTINEL,
+                Track1::parse,
+            ),
+            track2: ParsedTrack::parse(
+                &tracks_data.track2,
+                2,
+                TRACK2_START_SENTINEL,
+                Track2::parse,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Iso7813Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        ParsedTrack::fmt_track(&self.track1, 1, f)?;
+        writeln!(f)?;
+        ParsedTrack::fmt_track(&self.track2, 2, f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track1 {
+    pub format_code: char,
+    pub pan: String,
+    pub pan_luhn_valid: bool,
+    pub name: String,
+    /// YYMM
+    pub expiry: String,
+    pub service_code: String,
+    pub discretionary_data: String,
+}
+
+impl Track1 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track1, MsrxToolError> {
+        let mut chars = content.chars();
+        let format_code = chars.next().ok_or_else(|| invalid(1, "track is empty"))?;
+        if format_code != FORMAT_CODE_B {
+            return Err(invalid(
+                1,
+                &format!("format code {} is not supported, expected B", format_code),
+            ));
+        }
+        let mut fields = chars.as_str().splitn(3, TRACK1_FIELD_SEPARATOR);
+        let pan = parse_pan(1, fields.next().unwrap_or_default())?;
+        let (name, rest) = match (fields.next(), fields.next()) {
+            (Some(name), Some(rest)) => (name, rest),
+            _ => return Err(invalid(1, "name must be between two ^ field separators")),
+        };
+        if !(MIN_NAME_LENGTH..=MAX_NAME_LENGTH).contains(&name.len()) {
+            return Err(invalid(
+                1,
+                &format!(
+                    "name must be {}-{} characters",
+                    MIN_NAME_LENGTH, MAX_NAME_LENGTH
+                ),
+            ));
+        }
+        let (expiry, service_code, discretionary_data) = parse_additional_data(1, rest)?;
+
+        Ok(Track1 {
+            format_code,
+            pan_luhn_valid: check_digit::luhn_is_valid(&pan),
+            pan,
+            name: name.to_string(),
+            expiry,
+            service_code,
+            discretionary_data,
+        })
+    }
+}
+
+impl fmt::Display for Track1 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "format code {}, PAN {} ({}), name {}, expiry {}, service code {}, discretionary data {}",
+            self.format_code,
+            self.pan,
+            luhn_text(self.pan_luhn_valid),
+            self.name,
+            self.expiry,
+            self.service_code,
+            self.discretionary_data
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track2 {
+    pub pan: String,
+    pub pan_luhn_valid: bool,
+    /// YYMM
+    pub expiry: String,
+    pub service_code: String,
+    pub discretionary_data: String,
+}
+
+impl Track2 {
+    /// Parses track content without sentinels
+    pub fn parse(content: &str) -> Result<Track2, MsrxToolError> {
+        let (pan, rest) = content
+            .split_once(TRACK2_FIELD_SEPARATOR)
+            .ok_or_else(|| invalid(2, "field separator = is missing"))?;
+        let pan = parse_pan(2, pan)?;
+        let (expiry, service_code, discretionary_data) = parse_additional_data(2, rest)?;
+
+        Ok(Track2 {
+            pan_luhn_valid: check_digit::luhn_is_valid(&pan),
+            pan,
+            expiry,
+            service_code,
+            discretionary_data,
+        })
+    }
+}
+
+impl fmt::Display for Track2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PAN {} ({}), expiry {}, service code {}, discretionary data {}",
+            self.pan,
+            luhn_text(self.pan_luhn_valid),
+            self.expiry,
+            self.service_code,
+            self.discretionary_data
+        )
+    }
+}
+
+fn invalid(track_number: usize, reason: &str) -> MsrxToolError {
+    MsrxToolError::InvalidCardData(track_number, reason.to_string())
+}
+
+fn luhn_text(valid: bool) -> &'static str {
+    if valid {
+        "Luhn valid"
+    } else {
+        "Luhn invalid"
+    }
+}
+
+fn parse_pan(track_number: usize, pan: &str) -> Result<String, MsrxToolError> {
+    if pan.is_empty() || pan.len() > MAX_PAN_LENGTH || !pan.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid(
+            track_number,
+            &format!("PAN must be 1-{} digits", MAX_PAN_LENGTH),
+        ));
+    }
+    Ok(pan.to_string())
+}
+
+/// Splits expiry date, service code and discretionary data which follow the last field separator
+fn parse_additional_data(
+    track_number: usize,
+    data: &str,
+) -> Result<(String, String, String), MsrxToolError> {
+    let fixed_length = EXPIRY_LENGTH + SERVICE_CODE_LENGTH;
+    // Data is sliced only after the fixed fields are known to be ASCII digits
+    if !data
+        .get(..fixed_length)
+        .is_some_and(|fixed| fixed.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Err(invalid(
+            track_number,
+            "expiry date and service code must be 4 and 3 digits",
+        ));
+    }
+    Ok((
+        data[..EXPIRY_LENGTH].to_string(),
+        data[EXPIRY_LENGTH..fixed_length].to_string(),
+        data[fixed_length..].to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_format::DataFormat;
+    use crate::parse::{ParseFormat, ParsedCard};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_card() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str(
+            "%B4111111111111111^DOE/JOHN^2612101000000123?_;4111111111111111=26121010000123?",
+            &'_',
+        )?;
+
+        let card = Iso7813Card::parse(&tracks_data);
+
+        assert_eq!(
+            card.track1,
+            Some(ParsedTrack::Parsed(Track1 {
+                format_code: 'B',
+                pan: "4111111111111111".to_string(),
+                pan_luhn_valid: true,
+                name: "DOE/JOHN".to_string(),
+                expiry: "2612".to_string(),
+                service_code: "101".to_string(),
+                discretionary_data: "000000123".to_string(),
+            }))
+        );
+        assert_eq!(
+            card.track2,
+            Some(ParsedTrack::Parsed(Track2 {
+                pan: "4111111111111111".to_string(),
+                pan_luhn_valid: true,
+                expiry: "2612".to_string(),
+                service_code: "101".to_string(),
+                discretionary_data: "0000123".to_string(),
+            }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_luhn() -> Result<(), MsrxToolError> {
+        let track2 = Track2::parse("4111111111111112=2612101")?;
+
+        assert!(!track2.pan_luhn_valid);
+        assert_eq!(track2.discretionary_data, "");
+        assert_eq!(
+            track2.to_string(),
+            "PAN 4111111111111112 (Luhn invalid), expiry 2612, service code 101, discretionary data "
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let track1_errors = [
+            "",
+            "A4111111111111111^DOE/JOHN^2612101",
+            "B4111111111111111^DOE/JOHN",
+            "B41111111111111111111^DOE/JOHN^2612101",
+            "B4111^D^2612101",
+            "B4111^DOE/JOHN^26121",
+            "B4111^DOE/JOHN^26A2101",
+            "B4111^DOE/JOHN^261210é",
+        ];
+        for content in track1_errors {
+            assert!(
+                matches!(
+                    Track1::parse(content),
+                    Err(MsrxToolError::InvalidCardData(1, _))
+                ),
+                "{}",
+                content
+            );
+        }
+        for content in ["41112612101", "=2612101", "4111=26", "4111=261210é"] {
+            assert!(
+                matches!(
+                    Track2::parse(content),
+                    Err(MsrxToolError::InvalidCardData(2, _))
+                ),
+                "{}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_parsed_card_output() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str("%A123?__;1?", &'_')?;
+
+        let card = ParsedCard::parse(&tracks_data, ParseFormat::Iso7813);
+
+        assert_eq!(
+            serde_json::to_value(&card).unwrap(),
+            json!({
+                "format": "iso7813",
+                "track1": {"error": "Invalid card data on track 1: format code A is not supported, expected B"},
+                "track2": null
+            })
+        );
+        assert_eq!(
+            card.to_string(),
+            "Track 1: Invalid card data on track 1: format code A is not supported, expected B\n\
+            Track 2: no data"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_raw_track() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_raw_str("_ff", &'_')?;
+
+        let card = Iso7813Card::parse(&tracks_data);
+
+        assert_eq!(tracks_data.track2.format, DataFormat::Raw);
+        assert!(matches!(card.track2, Some(ParsedTrack::Error { .. })));
+        Ok(())
+    }
+}
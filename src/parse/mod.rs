@@ -0,0 +1,142 @@
+//! Parsers which split track data into the fields of a card standard
+
+pub mod aamva;
+pub mod iso4909;
+pub mod iso7813;
+
+use crate::data_format::DataFormat;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::track_data::TrackData;
+use crate::tracks_data::TracksData;
+use aamva::AamvaCard;
+use iso4909::Iso4909Card;
+use iso7813::Iso7813Card;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+const TRACK_END_SENTINEL: char = '?';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseFormat {
+    /// Payment cards, track 1 format B and track 2
+    Iso7813,
+    /// Financial transaction cards, track 3
+    Iso4909,
+    /// Driver licenses and ID cards
+    Aamva,
+}
+
+impl FromStr for ParseFormat {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iso7813" => Ok(ParseFormat::Iso7813),
+            "iso4909" => Ok(ParseFormat::Iso4909),
+            "aamva" => Ok(ParseFormat::Aamva),
+            _ => Err(MsrxToolError::UnsupportedParseFormat),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum ParsedCard {
+    Iso7813(Box<Iso7813Card>),
+    Iso4909(Box<Iso4909Card>),
+    Aamva(Box<AamvaCard>),
+}
+
+impl ParsedCard {
+    pub fn parse(tracks_data: &TracksData, format: ParseFormat) -> ParsedCard {
+        match format {
+            ParseFormat::Iso7813 => ParsedCard::Iso7813(Box::new(Iso7813Card::parse(tracks_data))),
+            ParseFormat::Iso4909 => ParsedCard::Iso4909(Box::new(Iso4909Card::parse(tracks_data))),
+            ParseFormat::Aamva => ParsedCard::Aamva(Box::new(AamvaCard::parse(tracks_data))),
+        }
+    }
+
+    pub fn format(&self) -> ParseFormat {
+        match self {
+            ParsedCard::Iso7813(_) => ParseFormat::Iso7813,
+            ParsedCard::Iso4909(_) => ParseFormat::Iso4909,
+            ParsedCard::Aamva(_) => ParseFormat::Aamva,
+        }
+    }
+}
+
+impl fmt::Display for ParsedCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsedCard::Iso7813(card) => write!(f, "{}", card),
+            ParsedCard::Iso4909(card) => write!(f, "{}", card),
+            ParsedCard::Aamva(card) => write!(f, "{}", card),
+        }
+    }
+}
+
+/// Result of parsing one track, serialized as the fields of the track or as `{"error": ...}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ParsedTrack<T> {
+    Parsed(T),
+    Error { error: String },
+}
+
+impl<T> ParsedTrack<T> {
+    /// Parses a track, tracks without data are `None`
+    fn parse(
+        track: &TrackData,
+        track_number: usize,
+        start_sentinel: char,
+        parse: impl FnOnce(&str) -> Result<T, MsrxToolError>,
+    ) -> Option<ParsedTrack<T>> {
+        if track.data.is_empty() {
+            return None;
+        }
+        let result =
+            track_content(track, track_number, start_sentinel).and_then(|content| parse(&content));
+        Some(match result {
+            Ok(fields) => ParsedTrack::Parsed(fields),
+            Err(e) => ParsedTrack::Error {
+                error: e.to_string(),
+            },
+        })
+    }
+
+    fn fmt_track(
+        track: &Option<ParsedTrack<T>>,
+        track_number: usize,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result
+    where
+        T: fmt::Display,
+    {
+        match track {
+            None => write!(f, "Track {}: no data", track_number),
+            Some(ParsedTrack::Parsed(fields)) => write!(f, "Track {}: {}", track_number, fields),
+            Some(ParsedTrack::Error { error }) => write!(f, "Track {}: {}", track_number, error),
+        }
+    }
+}
+
+/// Characters between the sentinels of an ISO track
+fn track_content(
+    track: &TrackData,
+    track_number: usize,
+    start_sentinel: char,
+) -> Result<String, MsrxToolError> {
+    let invalid = |reason: &str| MsrxToolError::InvalidCardData(track_number, reason.to_string());
+    if track.format == DataFormat::Raw {
+        return Err(invalid("raw data must be decoded first, use --decode-bpc"));
+    }
+    let text = track.to_string()?;
+    let content = text
+        .strip_prefix(start_sentinel)
+        .ok_or_else(|| invalid(&format!("start sentinel {} is missing", start_sentinel)))?;
+    let end = content
+        .find(TRACK_END_SENTINEL)
+        .ok_or_else(|| invalid(&format!("end sentinel {} is missing", TRACK_END_SENTINEL)))?;
+    Ok(content[..end].to_string())
+}
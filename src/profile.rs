@@ -0,0 +1,180 @@
+use crate::config::DeviceConfig;
+use crate::data_format::DataFormat;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::output::OutputFormat;
+use crate::redaction::Redaction;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const CONFIG_DIRECTORY: &str = "msrx-tool";
+const PROFILES_FILE: &str = "profiles.toml";
+
+/// Named device profile. Device fields are given at the top level of the profile table,
+/// options which are not given fall back to command line defaults.
+///
+/// ```toml
+/// [profiles.lo-co]
+/// is_hi_co = false
+/// read_timeout = 60
+/// output_format = "json"
+///
+/// [profiles.lo-co.track2]
+/// bpc = 7
+/// bpi = 210
+///
+/// [profiles.lo-co.redaction]
+/// hide_name = true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub device: DeviceConfig,
+    pub read_timeout: Option<u64>,
+    pub write_timeout: Option<u64>,
+    pub data_format: Option<DataFormat>,
+    pub output_format: Option<OutputFormat>,
+    pub format_separator: Option<char>,
+    pub redaction: Redaction,
+    /// Keys which are neither profile nor device fields. `deny_unknown_fields` doesn't work
+    /// together with `flatten`, so they are collected here and refused after loading.
+    #[serde(flatten)]
+    unknown_fields: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+impl Profile {
+    /// Location of profiles file: `$XDG_CONFIG_HOME/msrx-tool/profiles.toml`
+    pub fn profiles_path() -> Result<PathBuf, MsrxToolError> {
+        dirs::config_dir()
+            .map(|dir| dir.join(CONFIG_DIRECTORY).join(PROFILES_FILE))
+            .ok_or(MsrxToolError::ConfigDirectoryNotFound)
+    }
+
+    pub fn load(name: &str) -> Result<Profile, MsrxToolError> {
+        Self::load_from(&Self::profiles_path()?, name)
+    }
+
+    pub fn load_from(path: &Path, name: &str) -> Result<Profile, MsrxToolError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| MsrxToolError::ProfileFileError(format!("{}: {}", path.display(), e)))?;
+        Self::from_profiles_str(&content, name)
+    }
+
+    pub fn from_profiles_str(content: &str, name: &str) -> Result<Profile, MsrxToolError> {
+        let mut file = ProfilesFile::from_str(content)?;
+        let profile = file
+            .profiles
+            .remove(name)
+            .ok_or(MsrxToolError::ProfileNotFound(name.to_string()))?;
+        if let Some(key) = profile.unknown_fields.keys().next() {
+            return Err(MsrxToolError::ProfileFileError(format!(
+                "unknown field `{}` in profile {}",
+                key, name
+            )));
+        }
+        profile.device.validate()?;
+        Ok(profile)
+    }
+}
+
+impl FromStr for ProfilesFile {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|e| MsrxToolError::ProfileFileError(e.message().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES: &str = r#"
+        [profiles.lo-co]
+        is_hi_co = false
+        leading_zero210 = 30
+        read_timeout = 60
+        data_format = "raw"
+        output_format = "json"
+        format_separator = "|"
+
+        [profiles.lo-co.track2]
+        bpc = 7
+        bpi = 210
+        bpi75 = 0xc0
+        bpi210 = 0xc1
+
+        [profiles.lo-co.redaction]
+        hide_discretionary_data = true
+
+        [profiles.empty]
+
+        [profiles.broken.track1]
+        bpc = 4
+
+        [profiles.misspelled]
+        read_timout = 60
+    "#;
+
+    #[test]
+    fn test_load_profile() -> Result<(), MsrxToolError> {
+        let profile = Profile::from_profiles_str(PROFILES, "lo-co")?;
+
+        assert!(!profile.device.is_hi_co);
+        assert_eq!(profile.device.leading_zero_packets(), vec![30, 22]);
+        assert_eq!(profile.device.bpc_packets(), vec![7, 7, 5]);
+        assert_eq!(profile.device.track2.bpi_packets(2)?, vec![0xc1]);
+        assert_eq!(profile.device.vendor_id, 0x0801);
+        assert_eq!(profile.read_timeout, Some(60));
+        assert_eq!(profile.write_timeout, None);
+        assert_eq!(profile.data_format, Some(DataFormat::Raw));
+        assert_eq!(profile.output_format, Some(OutputFormat::Json));
+        assert_eq!(profile.format_separator, Some('|'));
+        assert!(profile.redaction.mask_pan);
+        assert!(profile.redaction.hide_discretionary_data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_empty_profile_uses_defaults() -> Result<(), MsrxToolError> {
+        let profile = Profile::from_profiles_str(PROFILES, "empty")?;
+
+        assert_eq!(profile.device.bpc_packets(), vec![7, 5, 5]);
+        assert!(profile.device.is_hi_co);
+        assert_eq!(profile.read_timeout, None);
+        assert_eq!(profile.data_format, None);
+        assert_eq!(profile.redaction, Redaction::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_profile_errors() {
+        assert_eq!(
+            Profile::from_profiles_str(PROFILES, "missing").unwrap_err(),
+            MsrxToolError::ProfileNotFound("missing".to_string())
+        );
+        assert_eq!(
+            Profile::from_profiles_str(PROFILES, "broken").unwrap_err(),
+            MsrxToolError::InvalidBpc(1, 4)
+        );
+        assert_eq!(
+            Profile::from_profiles_str(PROFILES, "misspelled").unwrap_err(),
+            MsrxToolError::ProfileFileError(
+                "unknown field `read_timout` in profile misspelled".to_string()
+            )
+        );
+        assert!(matches!(
+            Profile::from_profiles_str("[profiles.x]\nis_hi_co = \"yes\"", "x"),
+            Err(MsrxToolError::ProfileFileError(_))
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+use crate::original_device_data::OriginalDeviceData;
+
+pub struct RawData {
+    pub raw: OriginalDeviceData,
+}
@@ -0,0 +1,548 @@
+//! Redaction of card data in output. PANs are found from the structure of ISO tracks: the
+//! digits before the first field separator (`^` on track 1 format B, `=` on tracks 2 and 3).
+//! Digit runs shorter than a PAN, e.g. membership numbers, are left as they are. Tracks of a
+//! parsed card are redacted by the format of the card, so track text hides the same fields as
+//! the parsed card. Raw tracks have no known structure and are not redacted, so they are only
+//! shown with `--show-clear-data`.
+
+use crate::data_format::DataFormat;
+use crate::parse::aamva;
+use crate::parse::iso4909;
+use crate::parse::iso7813::{Track1, Track2};
+use crate::parse::{ParseFormat, ParsedCard, ParsedTrack};
+use crate::track_data::TrackData;
+use crate::tracks_data::TracksData;
+use crate::verify::TrackMismatch;
+use serde::Deserialize;
+
+pub const MASK_CHARACTER: char = '*';
+const MIN_PAN_LENGTH: usize = 12;
+const PAN_VISIBLE_START: usize = 6;
+const PAN_VISIBLE_END: usize = 4;
+// Expiry date and service code after the last field separator
+const ADDITIONAL_DATA_LENGTH: usize = 7;
+const TRACK1_FORMAT_CODE_B: char = 'B';
+const TRACK1_FIELD_SEPARATOR: char = '^';
+const TRACK2_3_FIELD_SEPARATOR: char = '=';
+const TRACK_END_SENTINEL: char = '?';
+
+/// What is hidden from output. Profile can set these in a `redaction` table.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Redaction {
+    /// Mask all but first six and last four digits of PANs. Subsidiary account numbers and card
+    /// security number of ISO 4909 track 3 and the ID of AAMVA track 3 are masked completely.
+    pub mask_pan: bool,
+    pub hide_name: bool,
+    pub hide_discretionary_data: bool,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Redaction {
+            mask_pan: true,
+            hide_name: false,
+            hide_discretionary_data: false,
+        }
+    }
+}
+
+impl Redaction {
+    /// Shows all data as it is on the card
+    pub fn clear() -> Self {
+        Redaction {
+            mask_pan: false,
+            hide_name: false,
+            hide_discretionary_data: false,
+        }
+    }
+
+    /// Redacts tracks, `format` is the format the card was parsed with
+    pub fn redact_tracks(
+        &self,
+        tracks_data: &TracksData,
+        format: Option<ParseFormat>,
+    ) -> TracksData {
+        TracksData {
+            track1: self.redact_track_data(1, &tracks_data.track1, format),
+            track2: self.redact_track_data(2, &tracks_data.track2, format),
+            track3: self.redact_track_data(3, &tracks_data.track3, format),
+            status: tracks_data.status,
+        }
+    }
+
+    fn redact_track_data(
+        &self,
+        track_number: usize,
+        track: &TrackData,
+        format: Option<ParseFormat>,
+    ) -> TrackData {
+        let data = match track.format {
+            DataFormat::Iso => self
+                .redact_track_as(format, track_number, &String::from_utf8_lossy(&track.data))
+                .into_bytes(),
+            // Raw bits are shown as they are, decode them with --decode-bpc to redact
+            DataFormat::Raw => track.data.clone(),
+        };
+        TrackData {
+            data,
+            format: track.format,
+            action: track.action,
+        }
+    }
+
+    /// Redacts track by the format of the card. Tracks which don't follow the format, and
+    /// tracks of cards which were not parsed, are redacted like ISO 7813 tracks.
+    fn redact_track_as(
+        &self,
+        format: Option<ParseFormat>,
+        track_number: usize,
+        track: &str,
+    ) -> String {
+        let redacted = split_track(track).and_then(|(start_sentinel, content, end)| {
+            let content = match (format, track_number) {
+                (Some(ParseFormat::Iso4909), 3) => self.redact_iso4909_track3_content(content),
+                (Some(ParseFormat::Aamva), 1) => self.redact_aamva_track1_content(content),
+                (Some(ParseFormat::Aamva), 2) => self.redact_aamva_track2_content(content),
+                (Some(ParseFormat::Aamva), 3) => self.redact_aamva_track3_content(content),
+                _ => None,
+            }?;
+            Some(format!("{}{}{}", start_sentinel, content, end))
+        });
+        redacted.unwrap_or_else(|| self.redact_track(track_number, track))
+    }
+
+    /// Redacts ISO track given with sentinels, tracks which have no known structure are left
+    /// as they are
+    pub fn redact_track(&self, track_number: usize, track: &str) -> String {
+        let (start_sentinel, content, end) = match split_track(track) {
+            Some(parts) => parts,
+            None => return track.to_string(),
+        };
+        let redacted = match track_number {
+            1 => match content.strip_prefix(TRACK1_FORMAT_CODE_B) {
+                Some(content) => format!("{}{}", TRACK1_FORMAT_CODE_B, self.redact_track1(content)),
+                None => content.to_string(),
+            },
+            _ => self.redact_track2_3(content),
+        };
+        format!("{}{}{}", start_sentinel, redacted, end)
+    }
+
+    fn redact_track1(&self, content: &str) -> String {
+        let mut fields = content.splitn(3, TRACK1_FIELD_SEPARATOR);
+        let pan = self.redact_pan(fields.next().unwrap_or_default());
+        match (fields.next(), fields.next()) {
+            (Some(name), Some(additional_data)) => [
+                pan,
+                self.redact_name(name),
+                self.redact_additional_data(additional_data),
+            ]
+            .join(&TRACK1_FIELD_SEPARATOR.to_string()),
+            (Some(name), None) => {
+                [pan, self.redact_name(name)].join(&TRACK1_FIELD_SEPARATOR.to_string())
+            }
+            _ => pan,
+        }
+    }
+
+    fn redact_track2_3(&self, content: &str) -> String {
+        match content.split_once(TRACK2_3_FIELD_SEPARATOR) {
+            Some((pan, additional_data)) => format!(
+                "{}{}{}",
+                self.redact_pan(pan),
+                TRACK2_3_FIELD_SEPARATOR,
+                self.redact_additional_data(additional_data)
+            ),
+            None => content.to_string(),
+        }
+    }
+
+    /// Masks digits which are long enough to be a PAN
+    pub fn redact_pan(&self, pan: &str) -> String {
+        if !self.mask_pan || pan.len() < MIN_PAN_LENGTH || !pan.chars().all(|c| c.is_ascii_digit())
+        {
+            return pan.to_string();
+        }
+        let masked = pan.len() - PAN_VISIBLE_START - PAN_VISIBLE_END;
+        format!(
+            "{}{}{}",
+            &pan[..PAN_VISIBLE_START],
+            mask(masked),
+            &pan[pan.len() - PAN_VISIBLE_END..]
+        )
+    }
+
+    /// Masks account data which is hidden with PANs
+    fn redact_account_data(&self, data: &str) -> String {
+        if self.mask_pan {
+            mask(data.chars().count())
+        } else {
+            data.to_string()
+        }
+    }
+
+    pub fn redact_name(&self, name: &str) -> String {
+        if self.hide_name {
+            mask(name.chars().count())
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn redact_discretionary_data(&self, data: &str) -> String {
+        if self.hide_discretionary_data {
+            mask(data.chars().count())
+        } else {
+            data.to_string()
+        }
+    }
+
+    /// Keeps expiry date and service code and hides the discretionary data after them
+    fn redact_additional_data(&self, data: &str) -> String {
+        match data.char_indices().nth(ADDITIONAL_DATA_LENGTH) {
+            Some((index, _)) => format!(
+                "{}{}",
+                &data[..index],
+                self.redact_discretionary_data(&data[index..])
+            ),
+            None => data.to_string(),
+        }
+    }
+
+    pub fn redact_mismatch(&self, mismatch: &TrackMismatch) -> TrackMismatch {
+        let track_number = usize::from(mismatch.track);
+        TrackMismatch {
+            track: mismatch.track,
+            expected: self.redact_track(track_number, &mismatch.expected),
+            actual: self.redact_track(track_number, &mismatch.actual),
+        }
+    }
+
+    pub fn redact_parsed(&self, parsed: &ParsedCard) -> ParsedCard {
+        match parsed {
+            ParsedCard::Iso7813(card) => {
+                let mut card = card.clone();
+                if let Some(ParsedTrack::Parsed(track1)) = &mut card.track1 {
+                    self.redact_iso7813_track1(track1);
+                }
+                if let Some(ParsedTrack::Parsed(track2)) = &mut card.track2 {
+                    self.redact_iso7813_track2(track2);
+                }
+                ParsedCard::Iso7813(card)
+            }
+            ParsedCard::Iso4909(card) => {
+                let mut card = card.clone();
+                if let Some(ParsedTrack::Parsed(track3)) = &mut card.track3 {
+                    self.redact_iso4909_track3(track3);
+                }
+                ParsedCard::Iso4909(card)
+            }
+            ParsedCard::Aamva(card) => {
+                let mut card = card.clone();
+                if let Some(ParsedTrack::Parsed(track1)) = &mut card.track1 {
+                    self.redact_aamva_track1(track1);
+                }
+                if let Some(ParsedTrack::Parsed(track2)) = &mut card.track2 {
+                    self.redact_aamva_track2(track2);
+                }
+                if let Some(ParsedTrack::Parsed(track3)) = &mut card.track3 {
+                    self.redact_aamva_track3(track3);
+                }
+                ParsedCard::Aamva(card)
+            }
+        }
+    }
+
+    fn redact_iso7813_track1(&self, track: &mut Track1) {
+        track.pan = self.redact_pan(&track.pan);
+        track.name = self.redact_name(&track.name);
+        track.discretionary_data = self.redact_discretionary_data(&track.discretionary_data);
+    }
+
+    fn redact_iso7813_track2(&self, track: &mut Track2) {
+        track.pan = self.redact_pan(&track.pan);
+        track.discretionary_data = self.redact_discretionary_data(&track.discretionary_data);
+    }
+
+    fn redact_iso4909_track3(&self, track: &mut iso4909::Track3) {
+        track.pan = self.redact_pan(&track.pan);
+        for number in [
+            &mut track.san1,
+            &mut track.san2,
+            &mut track.card_security_number,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *number = self.redact_account_data(number);
+        }
+        track.discretionary_data = self.redact_discretionary_data(&track.discretionary_data);
+    }
+
+    /// Masks take the place of the fields, so the track keeps its length
+    fn redact_iso4909_track3_content(&self, content: &str) -> Option<String> {
+        let mut track = iso4909::Track3::parse(content).ok()?;
+        self.redact_iso4909_track3(&mut track);
+        Some(track.content())
+    }
+
+    /// Address is hidden with the name as it identifies the holder as well
+    fn redact_aamva_track1(&self, track: &mut aamva::Track1) {
+        track.family_name = self.redact_name(&track.family_name);
+        for part in track.given_names.iter_mut().chain(track.address.iter_mut()) {
+            *part = self.redact_name(part);
+        }
+    }
+
+    /// Name and address are at the end of track 1 content
+    fn redact_aamva_track1_content(&self, content: &str) -> Option<String> {
+        let holder_start = aamva::Track1::holder_start(content).ok()?;
+        let (location, holder) = content.split_at(holder_start);
+        Some(format!("{}{}", location, self.redact_name_fields(holder)))
+    }
+
+    /// Hides name and address fields of AAMVA track 1 and keeps the separators between them
+    fn redact_name_fields(&self, fields: &str) -> String {
+        if !self.hide_name {
+            return fields.to_string();
+        }
+        fields
+            .chars()
+            .map(|c| match c {
+                aamva::TRACK1_FIELD_SEPARATOR | aamva::NAME_SEPARATOR => c,
+                _ => MASK_CHARACTER,
+            })
+            .collect()
+    }
+
+    /// ID number is masked the same way as on track 2, where it follows the IIN like a PAN
+    fn redact_aamva_track2(&self, track: &mut aamva::Track2) {
+        let number = self.redact_pan(&format!("{}{}", track.iin, track.id_number));
+        track.id_number = number[track.iin.len()..].to_string();
+    }
+
+    /// Track 2 has no discretionary data, only the ID number is masked
+    fn redact_aamva_track2_content(&self, content: &str) -> Option<String> {
+        let [id, overflow] = aamva::Track2::id_ranges(content).ok()?;
+        let number = format!("{}{}", &content[..id.end], &content[overflow.clone()]);
+        let number = self.redact_pan(&number);
+        let (id_number, id_overflow) = number.split_at(id.end);
+        let mut redacted = content.to_string();
+        redacted.replace_range(overflow, id_overflow);
+        redacted.replace_range(..id.end, id_number);
+        Some(redacted)
+    }
+
+    /// Postal code is part of the address
+    fn redact_aamva_track3(&self, track: &mut aamva::Track3) {
+        track.postal_code = self.redact_name(&track.postal_code);
+        track.id = self.redact_account_data(&track.id);
+    }
+
+    fn redact_aamva_track3_content(&self, content: &str) -> Option<String> {
+        let [postal_code, id] = aamva::Track3::holder_ranges(content).ok()?;
+        let mut redacted = content.to_string();
+        redacted.replace_range(
+            postal_code.clone(),
+            &self.redact_name(&content[postal_code]),
+        );
+        redacted.replace_range(id.clone(), &self.redact_account_data(&content[id]));
+        Some(redacted)
+    }
+}
+
+/// Splits ISO track into start sentinel, content, and end sentinel with anything after it
+fn split_track(track: &str) -> Option<(char, &str, &str)> {
+    let content_end = track.find(TRACK_END_SENTINEL).unwrap_or(track.len());
+    let mut chars = track[..content_end].chars();
+    let start_sentinel = chars.next()?;
+    Some((start_sentinel, chars.as_str(), &track[content_end..]))
+}
+
+fn mask(length: usize) -> String {
+    MASK_CHARACTER.to_string().repeat(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msrx_tool_error::MsrxToolError;
+    use crate::parse::ParseFormat;
+
+    const TRACK1: &str = "%B4111111111111111^DOE/JOHN^2612101000000123?";
+    const TRACK2: &str = ";4111111111111111=26121010000123?";
+
+    fn hide_all() -> Redaction {
+        Redaction {
+            mask_pan: true,
+            hide_name: true,
+            hide_discretionary_data: true,
+        }
+    }
+
+    #[test]
+    fn test_default_masks_pan() {
+        let redaction = Redaction::default();
+
+        assert_eq!(
+            redaction.redact_track(1, TRACK1),
+            "%B411111******1111^DOE/JOHN^2612101000000123?"
+        );
+        assert_eq!(
+            redaction.redact_track(2, TRACK2),
+            ";411111******1111=26121010000123?"
+        );
+    }
+
+    #[test]
+    fn test_hide_name_and_discretionary_data() {
+        let redaction = hide_all();
+
+        assert_eq!(
+            redaction.redact_track(1, TRACK1),
+            "%B411111******1111^********^2612101*********?"
+        );
+        assert_eq!(
+            redaction.redact_track(2, TRACK2),
+            ";411111******1111=2612101*******?"
+        );
+    }
+
+    #[test]
+    fn test_other_data_is_not_redacted() {
+        let redaction = hide_all();
+
+        for (track_number, track) in [
+            (1, "%HELLO WORLD?"),
+            (2, ";00000042=2612?"),
+            (3, ";1234567890123?"),
+            (2, ""),
+        ] {
+            assert_eq!(redaction.redact_track(track_number, track), track);
+        }
+        assert_eq!(Redaction::clear().redact_track(1, TRACK1), TRACK1);
+    }
+
+    #[test]
+    fn test_redact_tracks_and_parsed_card() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str(&format!("{}_{}", TRACK1, TRACK2), &'_')?;
+        let redaction = hide_all();
+
+        let redacted = redaction.redact_tracks(&tracks_data, None);
+        let parsed =
+            redaction.redact_parsed(&ParsedCard::parse(&tracks_data, ParseFormat::Iso7813));
+
+        assert_eq!(
+            redacted.track2.to_string()?,
+            ";411111******1111=2612101*******?"
+        );
+        assert!(redacted.track3.data.is_empty());
+        let ParsedCard::Iso7813(card) = parsed else {
+            panic!("Card was not parsed as ISO 7813: {:?}", parsed);
+        };
+        match card.track1 {
+            Some(ParsedTrack::Parsed(track1)) => {
+                assert_eq!(track1.pan, "411111******1111");
+                assert!(track1.pan_luhn_valid);
+                assert_eq!(track1.name, "********");
+                assert_eq!(track1.discretionary_data, "*********");
+            }
+            track1 => panic!("Track 1 was not parsed: {:?}", track1),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact_aamva_card() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str(
+            "%CAMOUNTAIN VIEW^DOE$JOHN^1 MAIN ST^?_;636014123456789=271219900115=?",
+            &'_',
+        )?;
+
+        let parsed = hide_all().redact_parsed(&ParsedCard::parse(&tracks_data, ParseFormat::Aamva));
+
+        let ParsedCard::Aamva(card) = parsed else {
+            panic!("Card was not parsed as AAMVA: {:?}", parsed);
+        };
+        match (card.track1, card.track2) {
+            (Some(ParsedTrack::Parsed(track1)), Some(ParsedTrack::Parsed(track2))) => {
+                assert_eq!(track1.city, "MOUNTAIN VIEW");
+                assert_eq!(track1.family_name, "***");
+                assert_eq!(track1.given_names, vec!["****".to_string()]);
+                assert_eq!(track1.address, vec!["*********".to_string()]);
+                assert_eq!(track2.iin, "636014");
+                assert_eq!(track2.id_number, "*****6789");
+                assert_eq!(
+                    redacted_track2(&tracks_data),
+                    format!(";636014{}=271219900115=?", track2.id_number)
+                );
+            }
+            tracks => panic!("Tracks were not parsed: {:?}", tracks),
+        }
+
+        let overflow = TracksData::from_str("_;6360141234567890123=7799199001011234?", &'_')?;
+        let redacted = hide_all().redact_tracks(&overflow, Some(ParseFormat::Aamva));
+        assert_eq!(
+            redacted.track2.to_string()?,
+            ";636014*************=7799199001011234?"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact_iso4909_card() -> Result<(), MsrxToolError> {
+        let tracks_data = TracksData::from_str(
+            "__;014111111111111111=84097825000125061233030000000001122=1=12345678==000000042?",
+            &'_',
+        )?;
+        let card = ParsedCard::parse(&tracks_data, ParseFormat::Iso4909);
+
+        let ParsedCard::Iso4909(redacted) = Redaction::default().redact_parsed(&card) else {
+            panic!("Card was not parsed as ISO 4909: {:?}", card);
+        };
+        match redacted.track3 {
+            Some(ParsedTrack::Parsed(track3)) => {
+                assert_eq!(track3.pan, "411111******1111");
+                assert_eq!(track3.san1.as_deref(), Some("********"));
+                assert_eq!(track3.san2, None);
+                assert_eq!(track3.discretionary_data, "42");
+            }
+            track3 => panic!("Track 3 was not parsed: {:?}", track3),
+        }
+        assert_eq!(Redaction::clear().redact_parsed(&card), card);
+        Ok(())
+    }
+
+    fn redacted_track2(tracks_data: &TracksData) -> String {
+        Redaction::default().redact_track(2, &tracks_data.track2.to_string().unwrap())
+    }
+
+    #[test]
+    fn test_redact_mismatch() {
+        let mismatch = TrackMismatch {
+            track: 2,
+            expected: TRACK2.to_string(),
+            actual: "".to_string(),
+        };
+
+        assert_eq!(
+            Redaction::default().redact_mismatch(&mismatch).to_string(),
+            "Track 2: expected \";411111******1111=26121010000123?\", read \"\""
+        );
+    }
+
+    #[test]
+    fn test_deserialize_profile_redaction() {
+        let redaction: Redaction = toml::from_str("hide_name = true").unwrap();
+
+        assert_eq!(
+            redaction,
+            Redaction {
+                mask_pan: true,
+                hide_name: true,
+                hide_discretionary_data: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::command::Command;
+use crate::original_device_data::OriginalDeviceData;
+
+/// Self tests defined in "MSR605 Programmer's Manual"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelfTest {
+    /// Checks that commands reach the device and responses come back
+    Communication,
+    /// Checks card sensor, requires swiping a card
+    Sensor,
+    /// Checks RAM of the device
+    Ram,
+}
+
+impl SelfTest {
+    pub fn all() -> Vec<SelfTest> {
+        vec![SelfTest::Communication, SelfTest::Sensor, SelfTest::Ram]
+    }
+
+    pub fn command(&self) -> Command {
+        match self {
+            SelfTest::Communication => Command::CommunicationTest,
+            SelfTest::Sensor => Command::SensorTest,
+            SelfTest::Ram => Command::RamTest,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            SelfTest::Communication => "Communication test",
+            SelfTest::Sensor => "Sensor test",
+            SelfTest::Ram => "RAM test",
+        }
+    }
+
+    pub fn requires_swipe(&self) -> bool {
+        *self == SelfTest::Sensor
+    }
+
+    pub fn passed(&self, response: &OriginalDeviceData) -> bool {
+        match self {
+            // Communication test answers with ESC y instead of ESC 0
+            SelfTest::Communication => response.data[1] == 0x1b && response.data[2] == 0x79,
+            SelfTest::Sensor | SelfTest::Ram => response.successful_operation(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msrx_tool_error::MsrxToolError;
+
+    fn response(payload: &[u8]) -> Result<OriginalDeviceData, MsrxToolError> {
+        let mut data = [0u8; 64];
+        data[0] = 0xc0 + payload.len() as u8;
+        data[1..1 + payload.len()].copy_from_slice(payload);
+        data.try_into()
+    }
+
+    #[test]
+    fn test_communication_test_response() -> Result<(), MsrxToolError> {
+        assert!(SelfTest::Communication.passed(&response(&[0x1b, 0x79])?));
+        assert!(!SelfTest::Communication.passed(&response(&[0x1b, 0x30])?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ram_test_response() -> Result<(), MsrxToolError> {
+        assert!(SelfTest::Ram.passed(&response(&[0x1b, 0x30])?));
+        assert!(!SelfTest::Ram.passed(&response(&[0x1b, 0x41])?));
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::msrx_tool_error::MsrxToolError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Last issued serial number. Saved to a file after every card so numbers are never issued twice.
+#[derive(Debug, PartialEq)]
+pub struct SerialCounter {
+    path: PathBuf,
+    last_issued: Option<u64>,
+}
+
+impl SerialCounter {
+    /// Loads the counter, missing file means no numbers have been issued yet
+    pub fn load(path: &Path) -> Result<SerialCounter, MsrxToolError> {
+        let error =
+            |e: String| MsrxToolError::CounterFileError(format!("{}: {}", path.display(), e));
+        let last_issued = match fs::read_to_string(path) {
+            Ok(content) => Some(
+                content
+                    .trim()
+                    .parse()
+                    .map_err(|_| error(format!("expected a number, got {}", content.trim())))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(error(e.to_string())),
+        };
+        Ok(SerialCounter {
+            path: path.to_path_buf(),
+            last_issued,
+        })
+    }
+
+    /// Next serial to issue. `start` can skip numbers but can't go back to issued numbers.
+    pub fn next(&self, start: Option<u64>) -> Result<u64, MsrxToolError> {
+        match (self.last_issued, start) {
+            (None, start) => Ok(start.unwrap_or(1)),
+            (Some(last), Some(start)) if start <= last => {
+                Err(MsrxToolError::SerialAlreadyIssued(start, last))
+            }
+            (Some(_), Some(start)) => Ok(start),
+            (Some(last), None) => last
+                .checked_add(1)
+                .ok_or(MsrxToolError::SerialAlreadyIssued(last, last)),
+        }
+    }
+
+    /// Saves the counter to a temporary file first, so an interrupted save keeps the old number
+    pub fn save(&mut self, serial: u64) -> Result<(), MsrxToolError> {
+        let error = |e: std::io::Error| {
+            MsrxToolError::CounterFileError(format!("{}: {}", self.path.display(), e))
+        };
+        let mut temporary = self.path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, format!("{}\n", serial)).map_err(error)?;
+        fs::rename(&temporary, &self.path).map_err(error)?;
+        self.last_issued = Some(serial);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_never_repeats() -> Result<(), MsrxToolError> {
+        let path = std::env::temp_dir().join(format!("msrx-counter-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut counter = SerialCounter::load(&path)?;
+        assert_eq!(counter.next(None)?, 1);
+        assert_eq!(counter.next(Some(100))?, 100);
+        counter.save(100)?;
+
+        let counter = SerialCounter::load(&path)?;
+        fs::remove_file(&path).unwrap();
+        assert_eq!(counter.next(None)?, 101);
+        assert_eq!(counter.next(Some(200))?, 200);
+        assert_eq!(
+            counter.next(Some(100)),
+            Err(MsrxToolError::SerialAlreadyIssued(100, 100))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_counter_file() {
+        let path =
+            std::env::temp_dir().join(format!("msrx-counter-invalid-{}.txt", std::process::id()));
+        fs::write(&path, "abc").unwrap();
+
+        let result = SerialCounter::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(MsrxToolError::CounterFileError(_))));
+    }
+}
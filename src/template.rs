@@ -0,0 +1,242 @@
+//! Card templates are track data in combined format with placeholders in braces:
+//!
+//! - `{serial}` is the serial number, `{serial:08}` pads it with zeros to 8 digits
+//! - `{luhn}` is the Luhn check digit of the digits right before the placeholder
+//! - any other name, e.g. `{expiry}`, is replaced with a variable given on command line
+//!
+//! For example `;{serial:08}{luhn}={expiry}?` with serial 42 and expiry 2612 gives
+//! `;000000422=2612?`.
+
+use crate::check_digit;
+use crate::data_format::DataFormat;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::tracks_data::TracksData;
+use std::collections::HashMap;
+
+const SERIAL: &str = "serial";
+const LUHN: &str = "luhn";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Serial { width: usize },
+    Luhn,
+    Variable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+    variables: HashMap<String, String>,
+}
+
+impl Template {
+    pub fn parse(
+        template: &str,
+        variables: HashMap<String, String>,
+    ) -> Result<Template, MsrxToolError> {
+        let mut parts = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                return Err(MsrxToolError::InvalidTemplate(format!(
+                    "unexpected }} at position {}",
+                    template.len() - rest.len() + start
+                )));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                MsrxToolError::InvalidTemplate("placeholder is not closed with }".to_string())
+            })? + start;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            parts.push(Self::parse_placeholder(&rest[start + 1..end], &variables)?);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        Ok(Template { parts, variables })
+    }
+
+    fn parse_placeholder(
+        placeholder: &str,
+        variables: &HashMap<String, String>,
+    ) -> Result<Part, MsrxToolError> {
+        let invalid =
+            || MsrxToolError::InvalidTemplate(format!("invalid placeholder {{{}}}", placeholder));
+        match placeholder.split_once(':') {
+            None if placeholder == SERIAL => Ok(Part::Serial { width: 0 }),
+            None if placeholder == LUHN => Ok(Part::Luhn),
+            None if variables.contains_key(placeholder) => {
+                Ok(Part::Variable(placeholder.to_string()))
+            }
+            None if placeholder.is_empty() => Err(invalid()),
+            None => Err(MsrxToolError::InvalidTemplate(format!(
+                "variable {} is not given",
+                placeholder
+            ))),
+            Some((SERIAL, width)) if width.starts_with('0') => width[1..]
+                .parse()
+                .map(|width| Part::Serial { width })
+                .map_err(|_| invalid()),
+            Some(_) => Err(invalid()),
+        }
+    }
+
+    /// Replaces placeholders for one card. Serial which doesn't fit in the padded width is an
+    /// error, so all cards of a run have the same length.
+    pub fn render(&self, serial: u64) -> Result<String, MsrxToolError> {
+        let mut text = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(part) => text.push_str(part),
+                Part::Serial { width } => {
+                    let serial = format!("{:0width$}", serial, width = *width);
+                    if *width > 0 && serial.len() > *width {
+                        return Err(MsrxToolError::InvalidTemplate(format!(
+                            "serial {} does not fit in {} digits",
+                            serial, width
+                        )));
+                    }
+                    text.push_str(&serial);
+                }
+                Part::Luhn => {
+                    let digits_start = text
+                        .rfind(|c: char| !c.is_ascii_digit())
+                        .map_or(0, |index| index + 1);
+                    let digits = &text[digits_start..];
+                    let check_digit = check_digit::luhn_check_digit(digits)
+                        .filter(|_| !digits.is_empty())
+                        .ok_or_else(|| {
+                            MsrxToolError::InvalidTemplate("{luhn} must follow digits".to_string())
+                        })?;
+                    text.push(char::from(b'0' + check_digit));
+                }
+                Part::Variable(name) => text.push_str(&self.variables[name]),
+            }
+        }
+        Ok(text)
+    }
+
+    /// Renders the card and validates it like track data given to the write command
+    pub fn tracks_data(
+        &self,
+        serial: u64,
+        data_format: DataFormat,
+        separator: &char,
+    ) -> Result<TracksData, MsrxToolError> {
+        let text = self.render(serial)?;
+        match data_format {
+            DataFormat::Iso => TracksData::from_str(&text, separator),
+            DataFormat::Raw => TracksData::from_raw_str(&text, separator),
+        }
+    }
+}
+
+/// Parses `NAME=VALUE` variable given on command line
+pub fn parse_variable(variable: &str) -> Result<(String, String), MsrxToolError> {
+    match variable.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(MsrxToolError::InvalidTemplate(format!(
+            "variable must be given as NAME=VALUE, got {}",
+            variable
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([("expiry".to_string(), "2612".to_string())])
+    }
+
+    #[test]
+    fn test_render() -> Result<(), MsrxToolError> {
+        let template = Template::parse("%B{serial}^MEMBER?_;{serial:08}={expiry}?", variables())?;
+
+        assert_eq!(template.render(42)?, "%B42^MEMBER?_;00000042=2612?");
+        assert_eq!(
+            template.render(12345678)?,
+            "%B12345678^MEMBER?_;12345678=2612?"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_luhn() -> Result<(), MsrxToolError> {
+        let template = Template::parse(";79927{serial:05}{luhn}=?", variables())?;
+
+        assert_eq!(template.render(39871)?, ";79927398713=?");
+
+        let template = Template::parse(";{serial:08}{luhn}={expiry}?", variables())?;
+        assert_eq!(template.render(42)?, ";000000422=2612?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_errors() -> Result<(), MsrxToolError> {
+        let template = Template::parse(";{serial:04}?", variables())?;
+        assert!(matches!(
+            template.render(10000),
+            Err(MsrxToolError::InvalidTemplate(_))
+        ));
+
+        let template = Template::parse(";={luhn}?", variables())?;
+        assert!(matches!(
+            template.render(1),
+            Err(MsrxToolError::InvalidTemplate(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for template in [
+            ";{serial?",
+            ";serial}?",
+            ";{}?",
+            ";{name}?",
+            ";{serial:8}?",
+            ";{serial:0x}?",
+            ";{luhn:1}?",
+        ] {
+            assert!(
+                matches!(
+                    Template::parse(template, variables()),
+                    Err(MsrxToolError::InvalidTemplate(_))
+                ),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn test_tracks_data_is_validated() -> Result<(), MsrxToolError> {
+        let template = Template::parse("_;{serial:040}?", variables())?;
+
+        assert!(matches!(
+            template.tracks_data(1, DataFormat::Iso, &'_'),
+            Err(MsrxToolError::DataForTrackIsTooLong(2, _, _))
+        ));
+
+        let template = Template::parse("_;{serial:08}={expiry}?", variables())?;
+        let tracks_data = template.tracks_data(7, DataFormat::Iso, &'_')?;
+        assert_eq!(tracks_data.track2.data, b";00000007=2612?".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_variable() {
+        assert_eq!(
+            parse_variable("expiry=2612"),
+            Ok(("expiry".to_string(), "2612".to_string()))
+        );
+        assert!(parse_variable("=2612").is_err());
+        assert!(parse_variable("expiry").is_err());
+    }
+}
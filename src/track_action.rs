@@ -0,0 +1,39 @@
+use crate::msrx_tool_error::MsrxToolError;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// What happens to a track when card is written
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackAction {
+    /// Track is left out of the data block and the device does not touch it
+    Leave,
+    /// Track is in the data block without data, so the device writes a blank track
+    Erase,
+    /// Track data is written
+    Write,
+}
+
+impl TrackAction {
+    /// Action which reproduces the track as it was read: empty tracks are blank
+    pub fn for_data(data: &[u8]) -> Self {
+        if data.is_empty() {
+            TrackAction::Erase
+        } else {
+            TrackAction::Write
+        }
+    }
+}
+
+impl FromStr for TrackAction {
+    type Err = MsrxToolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "leave" => Ok(TrackAction::Leave),
+            "erase" => Ok(TrackAction::Erase),
+            "write" => Ok(TrackAction::Write),
+            _ => Err(MsrxToolError::UnsupportedTrackAction),
+        }
+    }
+}
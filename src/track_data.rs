@@ -1,23 +1,72 @@
+use crate::char_bits_conversion::raw_track::{
+    bits_to_bytes, check_lrc, decode_track, encode_track,
+};
 use crate::data_format::DataFormat;
 use crate::msrx_tool_error::MsrxToolError;
+use crate::track_action::TrackAction;
 
 #[derive(Debug)]
 pub struct TrackData {
     pub data: Vec<u8>,
     pub format: DataFormat,
+    /// Used when the track is written
+    pub action: TrackAction,
 }
 impl TrackData {
+    /// Track data without sentinels, the device adds them when writing
     pub fn as_packets(&self) -> Vec<u8> {
-        if self.data.is_empty() {
-            return vec![0x00];
+        if self.data.len() < 2 {
+            vec![]
         } else {
-            return self.data[1..self.data.len() - 1].to_vec();
+            self.data[1..self.data.len() - 1].to_vec()
         }
     }
     pub fn to_string(&self) -> Result<String, MsrxToolError> {
         match self.format {
             DataFormat::Iso => self.to_string_iso(),
+            DataFormat::Raw => Ok(hex::encode(&self.data)),
+        }
+    }
+
+    /// Decodes raw data into ISO characters using given bits per character
+    pub fn decode_raw(
+        &self,
+        track_number: usize,
+        bits_per_character: u8,
+    ) -> Result<TrackData, MsrxToolError> {
+        match self.format {
+            DataFormat::Iso => Err(MsrxToolError::UnsupportedDataFormat),
+            DataFormat::Raw => Ok(TrackData {
+                data: decode_track(&self.data, track_number, bits_per_character)?.into_bytes(),
+                format: DataFormat::Iso,
+                action: self.action,
+            }),
+        }
+    }
+
+    /// Encodes ISO characters into raw data as they are on the stripe, followed by the LRC
+    pub fn encode_raw(&self, track_number: usize) -> Result<TrackData, MsrxToolError> {
+        let data = match self.format {
+            DataFormat::Iso if self.data.is_empty() => vec![],
+            DataFormat::Iso => bits_to_bytes(&encode_track(&self.to_string_iso()?, track_number)?),
             DataFormat::Raw => return Err(MsrxToolError::UnsupportedDataFormat),
+        };
+        Ok(TrackData {
+            data,
+            format: DataFormat::Raw,
+            action: self.action,
+        })
+    }
+
+    /// Checks LRC of raw data. ISO data has been checked by the device already.
+    pub fn check_lrc(
+        &self,
+        track_number: usize,
+        bits_per_character: u8,
+    ) -> Result<(), MsrxToolError> {
+        match self.format {
+            DataFormat::Iso => Ok(()),
+            DataFormat::Raw => check_lrc(&self.data, track_number, bits_per_character),
         }
     }
 
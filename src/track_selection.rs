@@ -0,0 +1,110 @@
+use crate::msrx_tool_error::MsrxToolError;
+
+/// Selection of tracks for commands that operate on some of the tracks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackSelection {
+    pub track1: bool,
+    pub track2: bool,
+    pub track3: bool,
+}
+
+impl TrackSelection {
+    #[cfg(test)]
+    pub fn all() -> Self {
+        TrackSelection {
+            track1: true,
+            track2: true,
+            track3: true,
+        }
+    }
+
+    pub fn from_track_numbers(track_numbers: &[u8]) -> Result<Self, MsrxToolError> {
+        let mut selection = TrackSelection {
+            track1: false,
+            track2: false,
+            track3: false,
+        };
+        for track_number in track_numbers {
+            match track_number {
+                1 => selection.track1 = true,
+                2 => selection.track2 = true,
+                3 => selection.track3 = true,
+                _ => return Err(MsrxToolError::InvalidTrackNumber(*track_number)),
+            }
+        }
+        Ok(selection)
+    }
+
+    /// Select byte for erase command, page 8 in "MSR605 Programmer's Manual".
+    /// Note that track 1 alone is 0x00, not 0x01
+    pub fn select_byte(&self) -> Result<u8, MsrxToolError> {
+        match (self.track1, self.track2, self.track3) {
+            (true, false, false) => Ok(0b000),
+            (false, true, false) => Ok(0b010),
+            (false, false, true) => Ok(0b100),
+            (true, true, false) => Ok(0b011),
+            (true, false, true) => Ok(0b101),
+            (false, true, true) => Ok(0b110),
+            (true, true, true) => Ok(0b111),
+            (false, false, false) => Err(MsrxToolError::NoTracksSelected),
+        }
+    }
+
+    /// Reverse of `select_byte`
+    pub fn from_select_byte(select_byte: u8) -> Result<Self, MsrxToolError> {
+        match select_byte {
+            0b000 => Self::from_track_numbers(&[1]),
+            0b010 => Self::from_track_numbers(&[2]),
+            0b100 => Self::from_track_numbers(&[3]),
+            0b011 => Self::from_track_numbers(&[1, 2]),
+            0b101 => Self::from_track_numbers(&[1, 3]),
+            0b110 => Self::from_track_numbers(&[2, 3]),
+            0b111 => Self::from_track_numbers(&[1, 2, 3]),
+            _ => Err(MsrxToolError::NoTracksSelected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_bytes() -> Result<(), MsrxToolError> {
+        let cases: [(&[u8], u8); 7] = [
+            (&[1], 0x00),
+            (&[2], 0x02),
+            (&[3], 0x04),
+            (&[1, 2], 0x03),
+            (&[1, 3], 0x05),
+            (&[2, 3], 0x06),
+            (&[3, 2, 1], 0x07),
+        ];
+
+        for (track_numbers, select_byte) in cases {
+            let selection = TrackSelection::from_track_numbers(track_numbers)?;
+            assert_eq!(selection.select_byte()?, select_byte);
+            assert_eq!(TrackSelection::from_select_byte(select_byte)?, selection);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_tracks_selected() -> Result<(), MsrxToolError> {
+        let selection = TrackSelection::from_track_numbers(&[])?;
+
+        assert_eq!(
+            selection.select_byte().unwrap_err(),
+            MsrxToolError::NoTracksSelected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_track_number() {
+        assert_eq!(
+            TrackSelection::from_track_numbers(&[1, 4]).unwrap_err(),
+            MsrxToolError::InvalidTrackNumber(4)
+        );
+    }
+}
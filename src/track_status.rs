@@ -1,10 +1,14 @@
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TrackStatus {
     Ok,
     WriteOrReadError,
     CommandFormatError,
     InvalidCommand,
     InvalidCardSwipeOnWrite,
+    EraseError,
     ParsedFromInput,
     Unknown,
 }
@@ -17,6 +21,7 @@ impl From<u8> for TrackStatus {
             0x32 => TrackStatus::CommandFormatError,
             0x34 => TrackStatus::InvalidCommand,
             0x39 => TrackStatus::InvalidCardSwipeOnWrite,
+            0x41 => TrackStatus::EraseError,
             _ => TrackStatus::Unknown,
         }
     }
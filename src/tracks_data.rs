@@ -1,6 +1,9 @@
+use crate::combined_format;
 use crate::data_format::DataFormat;
 use crate::iso_data::IsoData;
 use crate::msrx_tool_error::MsrxToolError;
+use crate::raw_data::RawData;
+use crate::track_action::TrackAction;
 use crate::track_data::TrackData;
 use crate::track_status::TrackStatus;
 use crate::tracks_data;
@@ -18,6 +21,8 @@ const TRACK_END_SENTINEL: char = '?';
 const TRACK1_MAX_LENGTH: usize = 79;
 const TRACK2_MAX_LENGTH: usize = 40;
 const TRACK3_MAX_LENGTH: usize = 107;
+// Length of raw track data is sent as one byte
+const RAW_TRACK_MAX_LENGTH: usize = 255;
 
 pub const TRACK1_SUPPORTED_ASCII: &str =
     " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_";
@@ -85,14 +90,88 @@ impl TryFrom<Vec<IsoData>> for TracksData {
             track1: TrackData {
                 data: tracks[0].clone(),
                 format: DataFormat::Iso,
+                action: TrackAction::for_data(&tracks[0]),
             },
             track2: TrackData {
                 data: tracks[1].clone(),
                 format: DataFormat::Iso,
+                action: TrackAction::for_data(&tracks[1]),
             },
             track3: TrackData {
                 data: tracks[2].clone(),
                 format: DataFormat::Iso,
+                action: TrackAction::for_data(&tracks[2]),
+            },
+            status,
+        })
+    }
+}
+
+impl TryFrom<Vec<RawData>> for TracksData {
+    type Error = MsrxToolError;
+
+    /// Raw data response: ESC s ESC 1 [L1][data] ESC 2 [L2][data] ESC 3 [L3][data] ? FS ESC [status]
+    /// where L1-L3 are lengths of the track data. Track data can contain any byte values so
+    /// lengths are used instead of looking for separators.
+    fn try_from(raw_datas: Vec<RawData>) -> Result<Self, Self::Error> {
+        let combined_raw_data: Vec<u8> = raw_datas
+            .iter()
+            .flat_map(|raw_data| {
+                let length = (raw_data.raw.data[0] & !(0x80 | 0x40)) as usize;
+                raw_data.raw.data[1..1 + length.min(63)].to_vec()
+            })
+            .collect();
+
+        if combined_raw_data.len() < 2 || combined_raw_data[0..2] != WRITE_BLOCK_START_FIELD {
+            return Err(MsrxToolError::RawDataNotCardData);
+        }
+
+        let mut tracks: Vec<Vec<u8>> = vec![];
+        let mut index = 2;
+        for (track_index, track_start_field) in [
+            TRACK_1_START_FIELD,
+            TRACK_2_START_FIELD,
+            TRACK_3_START_FIELD,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let track_number = track_index + 1;
+            if combined_raw_data.get(index..index + 2) != Some(&track_start_field[..]) {
+                return Err(MsrxToolError::InvalidRawTrackData(track_number));
+            }
+            let length = *combined_raw_data
+                .get(index + 2)
+                .ok_or(MsrxToolError::InvalidRawTrackData(track_number))?
+                as usize;
+            let data_start = index + 3;
+            let track = combined_raw_data
+                .get(data_start..data_start + length)
+                .ok_or(MsrxToolError::InvalidRawTrackData(track_number))?;
+            tracks.push(track.to_vec());
+            index = data_start + length;
+        }
+
+        let status = match combined_raw_data.get(index..index + 4) {
+            Some([0x3f, 0x1c, 0x1b, status_char]) => TrackStatus::from(*status_char),
+            _ => TrackStatus::Unknown,
+        };
+
+        Ok(TracksData {
+            track1: TrackData {
+                data: tracks[0].clone(),
+                format: DataFormat::Raw,
+                action: TrackAction::for_data(&tracks[0]),
+            },
+            track2: TrackData {
+                data: tracks[1].clone(),
+                format: DataFormat::Raw,
+                action: TrackAction::for_data(&tracks[1]),
+            },
+            track3: TrackData {
+                data: tracks[2].clone(),
+                format: DataFormat::Raw,
+                action: TrackAction::for_data(&tracks[2]),
             },
             status,
         })
@@ -100,30 +179,68 @@ impl TryFrom<Vec<IsoData>> for TracksData {
 }
 
 impl TracksData {
+    /// Decodes raw tracks into characters, each track using its own bits per character
+    pub fn decode_raw(&self, bits_per_character: &[u8; 3]) -> Result<TracksData, MsrxToolError> {
+        Ok(TracksData {
+            track1: self.track1.decode_raw(1, bits_per_character[0])?,
+            track2: self.track2.decode_raw(2, bits_per_character[1])?,
+            track3: self.track3.decode_raw(3, bits_per_character[2])?,
+            status: self.status,
+        })
+    }
+
+    /// Encodes ISO tracks into raw data with LRC, so they can be written with the raw write command
+    pub fn encode_raw(&self) -> Result<TracksData, MsrxToolError> {
+        Ok(TracksData {
+            track1: self.track1.encode_raw(1)?,
+            track2: self.track2.encode_raw(2)?,
+            track3: self.track3.encode_raw(3)?,
+            status: self.status,
+        })
+    }
+
+    /// Checks LRC of each raw track
+    pub fn check_lrc(&self, bits_per_character: &[u8; 3]) -> [Result<(), MsrxToolError>; 3] {
+        [
+            self.track1.check_lrc(1, bits_per_character[0]),
+            self.track2.check_lrc(2, bits_per_character[1]),
+            self.track3.check_lrc(3, bits_per_character[2]),
+        ]
+    }
+
+    /// Parses tracks given in combined format, see `combined_format` for escaping
     pub fn from_str(text: &str, separator: &char) -> Result<Self, MsrxToolError> {
-        let splits: Vec<&str> = text.split(*separator).collect();
+        let splits = combined_format::split(text, *separator)?;
+        Self::from_iso_tracks([
+            splits.first().map(String::as_str),
+            splits.get(1).map(String::as_str),
+            splits.get(2).map(String::as_str),
+        ])
+    }
 
+    /// Validates ISO track strings including sentinels. Missing or empty tracks are left as is
+    pub fn from_iso_tracks(tracks: [Option<&str>; 3]) -> Result<Self, MsrxToolError> {
         //Validation should be done in TrackData and not here, but due
         // to time constraint, no time to refactor or do it properly
-        let track1_data = Self::validate_track(
+        let track1 = Self::validate_track(
             1,
-            splits.get(0),
+            tracks[0],
             TRACK1_MAX_LENGTH,
             TRACK1_SUPPORTED_ASCII,
             TRACK_1_START_SENTINEL,
             TRACK_END_SENTINEL,
         )?;
-        let track2_data = Self::validate_track(
+        let track2 = Self::validate_track(
             2,
-            splits.get(1),
+            tracks[1],
             TRACK2_MAX_LENGTH,
             TRACK2_3_SUPPORTED_ASCII,
             TRACK2_3_START_SENTINEL,
             TRACK_END_SENTINEL,
         )?;
-        let track3_data = Self::validate_track(
+        let track3 = Self::validate_track(
             3,
-            splits.get(2),
+            tracks[2],
             TRACK3_MAX_LENGTH,
             TRACK2_3_SUPPORTED_ASCII,
             TRACK2_3_START_SENTINEL,
@@ -131,74 +248,177 @@ impl TracksData {
         )?;
 
         let tracks_data = TracksData {
-            track1: TrackData {
-                data: track1_data,
-                format: DataFormat::Iso,
-            },
-            track2: TrackData {
-                data: track2_data,
-                format: DataFormat::Iso,
-            },
-            track3: TrackData {
-                data: track3_data,
-                format: DataFormat::Iso,
-            },
+            track1,
+            track2,
+            track3,
             status: TrackStatus::ParsedFromInput,
         };
 
         Ok(tracks_data)
     }
 
-    /// Converts the data to a data block as it's defined in the manual
+    /// Parses raw track data given as hex strings, one per track. Empty track means no data
+    pub fn from_raw_str(text: &str, separator: &char) -> Result<Self, MsrxToolError> {
+        let splits = combined_format::split(text, *separator)?;
+        Self::from_raw_tracks([
+            splits.first().map(String::as_str),
+            splits.get(1).map(String::as_str),
+            splits.get(2).map(String::as_str),
+        ])
+    }
+
+    /// Parses raw track hex strings. Missing or empty tracks are left as is
+    pub fn from_raw_tracks(hex_tracks: [Option<&str>; 3]) -> Result<Self, MsrxToolError> {
+        let mut tracks: Vec<TrackData> = vec![];
+
+        for (track_index, hex_track) in hex_tracks.iter().enumerate() {
+            let track_number = track_index + 1;
+            let data = match hex_track {
+                Some(hex_data) => hex::decode(hex_data.trim())
+                    .map_err(|_| MsrxToolError::InvalidRawTrackData(track_number))?,
+                None => vec![],
+            };
+            if data.len() > RAW_TRACK_MAX_LENGTH {
+                return Err(MsrxToolError::RawDataForTrackIsTooLong(
+                    track_number,
+                    data.len(),
+                    RAW_TRACK_MAX_LENGTH,
+                ));
+            }
+            tracks.push(TrackData {
+                action: Self::action_for_input(&data),
+                data,
+                format: DataFormat::Raw,
+            });
+        }
+        let mut tracks = tracks.into_iter();
+
+        Ok(TracksData {
+            track1: tracks.next().unwrap(),
+            track2: tracks.next().unwrap(),
+            track3: tracks.next().unwrap(),
+            status: TrackStatus::ParsedFromInput,
+        })
+    }
+
+    /// Data format shared by all tracks
+    pub fn data_format(&self) -> Result<DataFormat, MsrxToolError> {
+        let format = self.track1.format;
+        if self.track2.format == format && self.track3.format == format {
+            Ok(format)
+        } else {
+            Err(MsrxToolError::MixedDataFormats)
+        }
+    }
+
+    /// Converts raw data to a data block for raw write command. Every track is
+    /// prefixed with the length of its data
+    /// Raw data block: ESC s ESC 1 [L1][data] ESC 2 [L2][data] ESC 3 [L3][data] ? FS.
+    /// Tracks which are left as is are omitted, erased tracks have zero length.
+    pub fn to_raw_data_block(&self) -> Result<Vec<u8>, MsrxToolError> {
+        let mut data_block = WRITE_BLOCK_START_FIELD.to_vec();
+
+        for (index, (track_start_field, track)) in self.with_start_fields().iter().enumerate() {
+            if track.data.len() > RAW_TRACK_MAX_LENGTH {
+                return Err(MsrxToolError::RawDataForTrackIsTooLong(
+                    index + 1,
+                    track.data.len(),
+                    RAW_TRACK_MAX_LENGTH,
+                ));
+            }
+            match track.action {
+                TrackAction::Leave => {}
+                // Zero length track is written without any bits, like in to_data_block
+                TrackAction::Erase => {
+                    data_block.extend(track_start_field);
+                    data_block.push(0);
+                }
+                TrackAction::Write => {
+                    data_block.extend(track_start_field);
+                    data_block.push(track.data.len() as u8);
+                    data_block.extend(&track.data);
+                }
+            }
+        }
+        data_block.extend(WRITE_BLOCK_END_FIELD);
+
+        Ok(data_block)
+    }
+
+    /// Converts the data to a data block as it's defined in the manual. Tracks which are
+    /// left as is are omitted, erased tracks have a start field without data.
     pub fn to_data_block(&self) -> Result<Vec<u8>, MsrxToolError> {
-        let card_data = TRACK_1_START_FIELD
-            .to_vec()
-            .into_iter()
-            .chain(self.track1.as_packets().clone())
-            .chain(TRACK_2_START_FIELD.to_vec())
-            .chain(self.track2.as_packets().clone())
-            .chain(TRACK_3_START_FIELD.to_vec())
-            .chain(self.track3.as_packets().clone())
-            .collect::<Vec<u8>>();
-
-        let data_block = WRITE_BLOCK_START_FIELD
-            .to_vec()
-            .into_iter()
-            .chain(card_data.clone())
-            .chain(WRITE_BLOCK_END_FIELD.to_vec())
-            .collect::<Vec<u8>>();
+        let mut data_block = WRITE_BLOCK_START_FIELD.to_vec();
+
+        for (track_start_field, track) in self.with_start_fields() {
+            match track.action {
+                TrackAction::Leave => {}
+                // The device writes a track whose start field has no data as a blank track, so
+                // the track is erased in the same swipe. Erase command (ESC c) would need another
+                // swipe of the same card.
+                TrackAction::Erase => data_block.extend(track_start_field),
+                TrackAction::Write => {
+                    data_block.extend(track_start_field);
+                    data_block.extend(track.as_packets());
+                }
+            }
+        }
+        data_block.extend(WRITE_BLOCK_END_FIELD);
 
         Ok(data_block)
     }
 
+    /// Sets action for tracks which have no data to write, e.g. to erase tracks which were not given
+    pub fn with_missing_tracks(mut self, action: TrackAction) -> Self {
+        for track in [&mut self.track1, &mut self.track2, &mut self.track3] {
+            if track.data.is_empty() && track.action != TrackAction::Write {
+                track.action = action;
+            }
+        }
+        self
+    }
+
+    fn with_start_fields(&self) -> [([u8; 2], &TrackData); 3] {
+        [
+            (TRACK_1_START_FIELD, &self.track1),
+            (TRACK_2_START_FIELD, &self.track2),
+            (TRACK_3_START_FIELD, &self.track3),
+        ]
+    }
+
+    fn action_for_input(data: &[u8]) -> TrackAction {
+        if data.is_empty() {
+            TrackAction::Leave
+        } else {
+            TrackAction::Write
+        }
+    }
+
     fn validate_track(
         track_number: usize,
-        data: Option<&&str>,
+        data: Option<&str>,
         track_max_length: usize,
         track_supported_ascii: &str,
         start_sentinel: char,
         end_sentinel: char,
-    ) -> Result<Vec<u8>, MsrxToolError> {
-        let data_vec = match data {
-            Some(data) => data.as_bytes().to_vec(),
-            None => vec![0x00],
-        };
+    ) -> Result<TrackData, MsrxToolError> {
+        let data_vec = data.unwrap_or_default().as_bytes().to_vec();
 
         if data_vec.len() > track_max_length {
-            Err(MsrxToolError::DataForTrackIsTooLong(
+            return Err(MsrxToolError::DataForTrackIsTooLong(
                 track_number,
                 data_vec.len(),
                 track_max_length,
-            ))
-        } else if data_vec != vec![0x00] {
+            ));
+        } else if !data_vec.is_empty() {
             if !data_vec
                 .iter()
                 .all(|&c| track_supported_ascii.contains(c as char))
             {
-                Err(MsrxToolError::InvalidTrackData(
+                return Err(MsrxToolError::InvalidTrackData(
                     track_number,
                     track_supported_ascii.to_string(),
-                ))
+                ));
             } else if data_vec[0] != start_sentinel as u8 {
                 return Err(MsrxToolError::InvalidStartSentinel(
                     track_number,
@@ -209,12 +429,14 @@ impl TracksData {
                     track_number,
                     end_sentinel,
                 ));
-            } else {
-                Ok(data_vec)
             }
-        } else {
-            Ok(data_vec)
         }
+
+        Ok(TrackData {
+            action: Self::action_for_input(&data_vec),
+            data: data_vec,
+            format: DataFormat::Iso,
+        })
     }
 }
 
@@ -359,6 +581,145 @@ mod tests {
         }
     }
 
+    mod raw_mode_track_data_read {
+        use super::*;
+
+        fn raw_data(payload: &[u8]) -> Result<RawData, MsrxToolError> {
+            let mut data = [0u8; 64];
+            data[0] = 0xc0 + payload.len() as u8;
+            data[1..1 + payload.len()].copy_from_slice(payload);
+            Ok(RawData {
+                raw: data.try_into()?,
+            })
+        }
+
+        #[test]
+        fn test_raw_data_to_tracks_data() -> Result<(), MsrxToolError> {
+            // Track 1 and Track 2 doesn't contain any data, Track 3 data is: ";1?"
+            let payload =
+                b"\x1b\x73\x1b\x01\x00\x1b\x02\x00\x1b\x03\x04\xaf\xc2\xb0\x00\x3f\x1c\x1b\x30";
+
+            let tracks_data: TracksData = vec![raw_data(payload)?].try_into()?;
+
+            assert_eq!(tracks_data.status, TrackStatus::Ok);
+            assert!(tracks_data.track1.data.is_empty());
+            assert!(tracks_data.track2.data.is_empty());
+            assert_eq!(tracks_data.track3.data, vec![0xaf, 0xc2, 0xb0, 0x00]);
+            assert_eq!(tracks_data.track3.to_string()?, "afc2b000");
+            assert_eq!(
+                tracks_data.decode_raw(&[7, 5, 5])?.track3.to_string()?,
+                ";1?"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn test_raw_data_with_separator_bytes_in_track_data() -> Result<(), MsrxToolError> {
+            let payload = b"\x1b\x73\x1b\x01\x03\x1b\x3f\x1c\x1b\x02\x02\x1b\x03\x1b\x03\x01\x3f\x3f\x1c\x1b\x31";
+
+            let tracks_data: TracksData = vec![raw_data(payload)?].try_into()?;
+
+            assert_eq!(tracks_data.status, TrackStatus::WriteOrReadError);
+            assert_eq!(tracks_data.track1.data, vec![0x1b, 0x3f, 0x1c]);
+            assert_eq!(tracks_data.track2.data, vec![0x1b, 0x03]);
+            assert_eq!(tracks_data.track3.data, vec![0x3f]);
+            Ok(())
+        }
+
+        #[test]
+        fn test_raw_data_with_too_short_track() -> Result<(), MsrxToolError> {
+            let payload = b"\x1b\x73\x1b\x01\x05\x01\x02\x03";
+
+            let result: Result<TracksData, MsrxToolError> = vec![raw_data(payload)?].try_into();
+
+            assert_eq!(result.unwrap_err(), MsrxToolError::InvalidRawTrackData(1));
+            Ok(())
+        }
+    }
+
+    mod raw_mode_write {
+        use super::*;
+
+        #[test]
+        fn test_from_raw_str() -> Result<(), MsrxToolError> {
+            let tracks_data = TracksData::from_raw_str("00ff1b__afc2b000", &'_')?;
+
+            assert_eq!(tracks_data.track1.data, vec![0x00, 0xff, 0x1b]);
+            assert!(tracks_data.track2.data.is_empty());
+            assert_eq!(tracks_data.track3.data, vec![0xaf, 0xc2, 0xb0, 0x00]);
+            assert_eq!(tracks_data.data_format()?, DataFormat::Raw);
+            Ok(())
+        }
+
+        #[test]
+        fn test_from_raw_str_invalid_hex() {
+            let result = TracksData::from_raw_str("00_0g", &'_');
+
+            assert_eq!(result.unwrap_err(), MsrxToolError::InvalidRawTrackData(2));
+        }
+
+        #[test]
+        fn test_from_raw_str_too_long() {
+            let result = TracksData::from_raw_str(&"00".repeat(256), &'_');
+
+            assert_eq!(
+                result.unwrap_err(),
+                MsrxToolError::RawDataForTrackIsTooLong(1, 256, 255)
+            );
+        }
+
+        #[test]
+        fn test_to_raw_data_block() -> Result<(), MsrxToolError> {
+            let tracks_data = TracksData::from_raw_str("1b3f__afc2", &'_')?;
+
+            let data_block = tracks_data.to_raw_data_block()?;
+
+            let expected_data_block = *b"\x1b\x73\x1b\x01\x02\x1b\x3f\x1b\x03\x02\xaf\xc2\x3f\x1c";
+            assert_eq!(expected_data_block.to_vec(), data_block);
+            Ok(())
+        }
+
+        #[test]
+        fn test_to_raw_data_block_track_actions() -> Result<(), MsrxToolError> {
+            let expected = [
+                (
+                    [TrackAction::Write, TrackAction::Erase, TrackAction::Leave],
+                    b"\x1b\x73\x1b\x01\x01\xff\x1b\x02\x00\x3f\x1c".to_vec(),
+                ),
+                (
+                    [TrackAction::Leave, TrackAction::Write, TrackAction::Erase],
+                    b"\x1b\x73\x1b\x02\x01\xee\x1b\x03\x00\x3f\x1c".to_vec(),
+                ),
+                (
+                    [TrackAction::Leave, TrackAction::Leave, TrackAction::Leave],
+                    b"\x1b\x73\x3f\x1c".to_vec(),
+                ),
+            ];
+
+            for (actions, data_block) in expected {
+                let mut tracks_data = TracksData::from_raw_str("ff_ee_dd", &'_')?;
+                tracks_data.track1.action = actions[0];
+                tracks_data.track2.action = actions[1];
+                tracks_data.track3.action = actions[2];
+
+                assert_eq!(tracks_data.to_raw_data_block()?, data_block);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn test_mixed_data_formats() -> Result<(), MsrxToolError> {
+            let mut tracks_data = TracksData::from_raw_str("00_00_00", &'_')?;
+            tracks_data.track2.format = DataFormat::Iso;
+
+            assert_eq!(
+                tracks_data.data_format().unwrap_err(),
+                MsrxToolError::MixedDataFormats
+            );
+            Ok(())
+        }
+    }
+
     mod from_str {
         use super::*;
         #[test]
@@ -379,8 +740,8 @@ mod tests {
                 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x30, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47,
                 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x3f,
             ];
-            let expected_track2: Vec<u8> = vec![0x00];
-            let expected_track3: Vec<u8> = vec![0x00];
+            let expected_track2: Vec<u8> = vec![];
+            let expected_track3: Vec<u8> = vec![];
 
             assert_eq!(expected_track1, result.track1.data);
             assert_eq!(expected_track2, result.track2.data);
@@ -413,7 +774,7 @@ mod tests {
                 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30, 0x39, 0x38, 0x37, 0x36, 0x35, 0x34,
                 0x33, 0x32, 0x31, 0x30, 0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x3f,
             ];
-            let expected_track3: Vec<u8> = vec![0x00];
+            let expected_track3: Vec<u8> = vec![];
 
             assert_eq!(expected_track1, result.track1.data);
             assert_eq!(expected_track2, result.track2.data);
@@ -455,6 +816,17 @@ mod tests {
             Ok(())
         }
 
+        #[test]
+        fn test_parse_text_escaped_separator_and_empty_track() -> Result<(), MsrxToolError> {
+            let result = TracksData::from_str("%A\\_B\\\\?__;1?", &'_')?;
+
+            assert_eq!(result.track1.data, b"%A_B\\?".to_vec());
+            assert!(result.track2.data.is_empty());
+            assert_eq!(result.track2.action, TrackAction::Leave);
+            assert_eq!(result.track3.data, b";1?".to_vec());
+            Ok(())
+        }
+
         #[test]
         fn test_from_str_validate_track1_characters_valid_chars() -> Result<(), MsrxToolError> {
             let data_to_parse = format!("%{}?_;1?_;1?", "1".repeat(30));
@@ -731,28 +1103,26 @@ mod tests {
     mod to_packets {
         use super::*;
 
+        fn track(data: &[u8], action: TrackAction) -> TrackData {
+            TrackData {
+                data: data.to_vec(),
+                format: DataFormat::Iso,
+                action,
+            }
+        }
+
         #[test]
         fn test_to_packets_one_track() -> Result<(), MsrxToolError> {
             let tracks_data = TracksData {
-                track1: TrackData {
-                    data: vec![0x41, 0x42, 0x43, 0x31, 0x32, 0x33],
-                    format: DataFormat::Iso,
-                },
-                track2: TrackData {
-                    data: vec![],
-                    format: DataFormat::Iso,
-                },
-                track3: TrackData {
-                    data: vec![],
-                    format: DataFormat::Iso,
-                },
+                track1: track(b"%ABC123?", TrackAction::Write),
+                track2: track(b"", TrackAction::Leave),
+                track3: track(b"", TrackAction::Leave),
                 status: TrackStatus::ParsedFromInput,
             };
 
             let packets = tracks_data.to_data_block()?;
 
-            let expected_packets =
-                *b"\x1b\x73\x1b\x01\x41\x42\x43\x31\x32\x33\x1b\x02\x00\x1b\x03\x00\x3f\x1c";
+            let expected_packets = *b"\x1b\x73\x1b\x01\x41\x42\x43\x31\x32\x33\x3f\x1c";
 
             assert_eq!(&expected_packets.to_vec(), &packets);
 
@@ -762,25 +1132,15 @@ mod tests {
         #[test]
         fn test_to_packets_one_track_middle_track() -> Result<(), MsrxToolError> {
             let tracks_data = TracksData {
-                track1: TrackData {
-                    data: vec![],
-                    format: DataFormat::Iso,
-                },
-                track2: TrackData {
-                    data: vec![0x41, 0x42, 0x43, 0x31, 0x32, 0x33],
-                    format: DataFormat::Iso,
-                },
-                track3: TrackData {
-                    data: vec![],
-                    format: DataFormat::Iso,
-                },
+                track1: track(b"", TrackAction::Leave),
+                track2: track(b";12345?", TrackAction::Write),
+                track3: track(b"", TrackAction::Leave),
                 status: TrackStatus::ParsedFromInput,
             };
 
             let packets = tracks_data.to_data_block()?;
 
-            let expected_packets =
-                *b"\x1b\x73\x1b\x01\x00\x1b\x02\x41\x42\x43\x31\x32\x33\x1b\x03\x00\x3f\x1c";
+            let expected_packets = *b"\x1b\x73\x1b\x02\x31\x32\x33\x34\x35\x3f\x1c";
 
             assert_eq!(&expected_packets.to_vec(), &packets);
 
@@ -790,24 +1150,16 @@ mod tests {
         #[test]
         fn test_to_packets_two_tracks() -> Result<(), MsrxToolError> {
             let tracks_data = TracksData {
-                track1: TrackData {
-                    data: vec![0x41, 0x42, 0x43, 0x31, 0x32, 0x33],
-                    format: DataFormat::Iso,
-                },
-                track2: TrackData {
-                    data: vec![0x31, 0x32, 0x33, 0x34, 0x35],
-                    format: DataFormat::Iso,
-                },
-                track3: TrackData {
-                    data: vec![],
-                    format: DataFormat::Iso,
-                },
+                track1: track(b"%ABC123?", TrackAction::Write),
+                track2: track(b";12345?", TrackAction::Write),
+                track3: track(b"", TrackAction::Leave),
                 status: TrackStatus::ParsedFromInput,
             };
 
             let packets = tracks_data.to_data_block()?;
 
-            let expected_packets = *b"\x1b\x73\x1b\x01\x41\x42\x43\x31\x32\x33\x1b\x02\x31\x32\x33\x34\x35\x1b\x03\x00\x3f\x1c";
+            let expected_packets =
+                *b"\x1b\x73\x1b\x01\x41\x42\x43\x31\x32\x33\x1b\x02\x31\x32\x33\x34\x35\x3f\x1c";
 
             assert_eq!(&expected_packets.to_vec(), &packets);
 
@@ -817,18 +1169,9 @@ mod tests {
         #[test]
         fn test_to_data_block_three_tracks_one_packet() -> Result<(), MsrxToolError> {
             let tracks_data = TracksData {
-                track1: TrackData {
-                    data: vec![0x41, 0x42, 0x43, 0x31, 0x32, 0x33],
-                    format: DataFormat::Iso,
-                },
-                track2: TrackData {
-                    data: vec![0x31, 0x32, 0x33, 0x34, 0x35],
-                    format: DataFormat::Iso,
-                },
-                track3: TrackData {
-                    data: vec![0x31, 0x32, 0x33, 0x34, 0x35],
-                    format: DataFormat::Iso,
-                },
+                track1: track(b"%ABC123?", TrackAction::Write),
+                track2: track(b";12345?", TrackAction::Write),
+                track3: track(b";12345?", TrackAction::Write),
                 status: TrackStatus::ParsedFromInput,
             };
 
@@ -842,45 +1185,73 @@ mod tests {
 
         #[test]
         fn test_to_data_block_three_tracks_multiple_packets() -> Result<(), MsrxToolError> {
-            let tracks_data = TracksData {
-                track1: TrackData {
-                    data: vec![
-                        0x25, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b,
-                        0x4c, 0x4d, 0x4e, 0x4f, 0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x31, 0x32,
-                        0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x30, 0x41, 0x42, 0x43, 0x44,
-                        0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x4f, 0x50,
-                        0x51, 0x52, 0x53, 0x54, 0x55, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37,
-                        0x38, 0x39, 0x30, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
-                        0x4a, 0x4b, 0x4c, 0x4d, 0x4e, 0x3f,
-                    ],
-                    format: DataFormat::Iso,
-                },
-                track2: TrackData {
-                    data: vec![
-                        0x3b, 0x30, 0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30,
-                        0x39, 0x38, 0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30, 0x39, 0x38,
-                        0x37, 0x36, 0x35, 0x34, 0x33, 0x32, 0x31, 0x30, 0x39, 0x38, 0x37, 0x36,
-                        0x35, 0x34, 0x3f,
-                    ],
-                    format: DataFormat::Iso,
-                },
-                track3: TrackData {
-                    data: vec![0x3b, 0x31, 0x32, 0x33, 0x34, 0x35, 0x3f],
-                    format: DataFormat::Iso,
-                },
-                status: TrackStatus::ParsedFromInput,
-            };
+            let tracks_data = TracksData::from_str(
+                "%ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMN?_\
+                ;0987654321098765432109876543210987654?_;12345?",
+                &'_',
+            )?;
 
             let packets = tracks_data.to_data_block()?;
 
             let expected_packet = *b"\
-            \x1b\x73\x1b\x01\x25\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x50\x51\x52\x53\x54\x55\x31\x32\x33\x34\x35\x36\x37\x38\x39\x30\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x50\x51\x52\x53\x54\x55\x31\x32\x33\x34\x35\x36\
-            \x37\x38\x39\x30\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x3f\x1b\x02\x3b\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x3f\x1b\x03\x3b\
-            \x31\x32\x33\x34\x35\x3f\x3f\x1c";
+            \x1b\x73\x1b\x01\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x50\x51\x52\x53\x54\x55\x31\x32\x33\x34\x35\x36\x37\x38\x39\x30\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x4f\x50\x51\x52\x53\x54\x55\x31\x32\x33\x34\x35\x36\
+            \x37\x38\x39\x30\x41\x42\x43\x44\x45\x46\x47\x48\x49\x4a\x4b\x4c\x4d\x4e\x1b\x02\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x33\x32\x31\x30\x39\x38\x37\x36\x35\x34\x1b\x03\
+            \x31\x32\x33\x34\x35\x3f\x1c";
 
             assert_eq!(&expected_packet.to_vec(), &packets);
 
             Ok(())
         }
+
+        #[test]
+        fn test_to_data_block_all_track_action_combinations() -> Result<(), MsrxToolError> {
+            let actions = [TrackAction::Leave, TrackAction::Erase, TrackAction::Write];
+            let fields: [(&[u8], &[u8]); 3] = [
+                (b"%A?", b"\x1b\x01A"),
+                (b";2?", b"\x1b\x022"),
+                (b";3?", b"\x1b\x033"),
+            ];
+
+            for action1 in actions {
+                for action2 in actions {
+                    for action3 in actions {
+                        let tracks_data = TracksData {
+                            track1: track(fields[0].0, action1),
+                            track2: track(fields[1].0, action2),
+                            track3: track(fields[2].0, action3),
+                            status: TrackStatus::ParsedFromInput,
+                        };
+
+                        let mut expected = WRITE_BLOCK_START_FIELD.to_vec();
+                        for (action, (_, field)) in [action1, action2, action3].iter().zip(fields) {
+                            match action {
+                                TrackAction::Leave => {}
+                                TrackAction::Erase => expected.extend(&field[0..2]),
+                                TrackAction::Write => expected.extend(field),
+                            }
+                        }
+                        expected.extend(WRITE_BLOCK_END_FIELD);
+
+                        assert_eq!(tracks_data.to_data_block()?, expected);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn test_with_missing_tracks() -> Result<(), MsrxToolError> {
+            let tracks_data =
+                TracksData::from_str("%A?", &'_')?.with_missing_tracks(TrackAction::Erase);
+
+            assert_eq!(tracks_data.track1.action, TrackAction::Write);
+            assert_eq!(tracks_data.track2.action, TrackAction::Erase);
+            assert_eq!(tracks_data.track3.action, TrackAction::Erase);
+            assert_eq!(
+                tracks_data.to_data_block()?,
+                b"\x1b\x73\x1b\x01A\x1b\x02\x1b\x03\x3f\x1c".to_vec()
+            );
+            Ok(())
+        }
     }
 }
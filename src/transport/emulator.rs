@@ -0,0 +1,872 @@
+use crate::char_bits_conversion::raw_track::{bits_to_bytes, decode_track, encode_track};
+use crate::led::Led;
+use crate::msrx_tool_error::MsrxToolError;
+use crate::track_selection::TrackSelection;
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const ESC: u8 = 0x1b;
+const STATUS_OK: u8 = 0x30;
+const FS: u8 = 0x1c;
+
+/// Card held by the emulator. Tracks are stored as raw bytes like they are on the stripe
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VirtualCard {
+    pub tracks: [Vec<u8>; 3],
+}
+
+impl VirtualCard {
+    /// Creates a card with ISO data, tracks must include sentinels
+    #[cfg(test)]
+    pub fn from_tracks(track1: &str, track2: &str, track3: &str) -> VirtualCard {
+        VirtualCard {
+            tracks: [
+                Self::encode(track1.as_bytes(), 1),
+                Self::encode(track2.as_bytes(), 2),
+                Self::encode(track3.as_bytes(), 3),
+            ],
+        }
+    }
+
+    /// Encodes track characters as they would be on the stripe, with leading zeros
+    fn encode(track: &[u8], track_number: usize) -> Vec<u8> {
+        if track.is_empty() {
+            return vec![];
+        }
+        let text = String::from_utf8_lossy(track);
+        match encode_track(&text, track_number) {
+            Ok(bits) => bits_to_bytes(&format!("{}{}", "0".repeat(8), bits)),
+            Err(_) => vec![],
+        }
+    }
+}
+
+/// What happens when the emulator is waiting for a card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Swipe {
+    Card,
+    NoCard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PendingOperation {
+    ReadIso,
+    ReadRaw,
+    WriteIso,
+    WriteRaw,
+    Erase(u8),
+    SensorTest,
+}
+
+/// Software MSR605/MSRX6 compatible device.
+///
+/// Understands the same ESC commands as the real device and answers with 64 byte interrupt
+/// reports. Operations that need a card swipe consume the next scripted `Swipe`, when there
+/// are no scripted swipes `auto_swipe` decides whether the card is swiped or not.
+#[derive(Debug)]
+pub struct Emulator {
+    pub card: VirtualCard,
+    pub firmware: String,
+    pub model: String,
+    pub bpc: [u8; 3],
+    pub bpi: Vec<u8>,
+    pub is_hi_co: bool,
+    pub leading_zeros: [u8; 2],
+    /// Every LED change, the last one is the current state
+    pub led_history: Vec<Led>,
+    pub auto_swipe: bool,
+    pub ram_failure: bool,
+    swipes: VecDeque<Swipe>,
+    incoming: Vec<u8>,
+    responses: VecDeque<[u8; 64]>,
+    pending: Option<PendingOperation>,
+    pending_payload: Vec<u8>,
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Emulator {
+            card: VirtualCard::default(),
+            firmware: "REVT3.12".to_string(),
+            model: "3S".to_string(),
+            bpc: [7, 5, 5],
+            bpi: vec![],
+            is_hi_co: true,
+            leading_zeros: [61, 22],
+            led_history: vec![],
+            auto_swipe: false,
+            ram_failure: false,
+            swipes: VecDeque::new(),
+            incoming: vec![],
+            responses: VecDeque::new(),
+            pending: None,
+            pending_payload: vec![],
+        }
+    }
+}
+
+impl Emulator {
+    pub fn with_card(card: VirtualCard) -> Emulator {
+        Emulator {
+            card,
+            auto_swipe: true,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn queue_swipe(&mut self, swipe: Swipe) {
+        self.swipes.push_back(swipe);
+    }
+
+    fn handle_command(&mut self, packets: Vec<u8>) {
+        if packets.len() < 2 || packets[0] != ESC {
+            self.queue_response(&[ESC, 0x34]);
+            return;
+        }
+        let payload = &packets[2..];
+
+        match packets[1] {
+            0x61 => self.reset_state(),
+            0x76 => {
+                let response = Self::text_response(&self.firmware);
+                self.queue_response(&response);
+            }
+            0x74 => {
+                let response = Self::text_response(&self.model);
+                self.queue_response(&response);
+            }
+            0x6f if payload.len() == 3 => {
+                self.bpc = [payload[0], payload[1], payload[2]];
+                self.queue_response(&[ESC, STATUS_OK, payload[0], payload[1], payload[2]]);
+            }
+            0x62 if payload.len() == 1 => {
+                self.bpi.push(payload[0]);
+                self.queue_response(&[ESC, STATUS_OK]);
+            }
+            0x78 => {
+                self.is_hi_co = true;
+                self.queue_response(&[ESC, STATUS_OK]);
+            }
+            0x79 => {
+                self.is_hi_co = false;
+                self.queue_response(&[ESC, STATUS_OK]);
+            }
+            0x7a if payload.len() == 2 => {
+                self.leading_zeros = [payload[0], payload[1]];
+                self.queue_response(&[ESC, STATUS_OK]);
+            }
+            0x72 => self.pending = Some(PendingOperation::ReadIso),
+            0x6d => self.pending = Some(PendingOperation::ReadRaw),
+            0x77 => {
+                self.pending = Some(PendingOperation::WriteIso);
+                self.pending_payload = payload.to_vec();
+            }
+            0x6e => {
+                self.pending = Some(PendingOperation::WriteRaw);
+                self.pending_payload = payload.to_vec();
+            }
+            0x63 if payload.len() == 1 => self.pending = Some(PendingOperation::Erase(payload[0])),
+            0x65 => self.queue_response(&[ESC, 0x79]),
+            0x86 => self.pending = Some(PendingOperation::SensorTest),
+            0x87 => {
+                let status = if self.ram_failure { 0x41 } else { STATUS_OK };
+                self.queue_response(&[ESC, status]);
+            }
+            0x81 => self.led_history.push(Led::Off),
+            0x82 => self.led_history.push(Led::All),
+            0x83 => self.led_history.push(Led::Green),
+            0x84 => self.led_history.push(Led::Yellow),
+            0x85 => self.led_history.push(Led::Red),
+            _ => self.queue_response(&[ESC, 0x34]),
+        }
+    }
+
+    fn reset_state(&mut self) {
+        self.responses.clear();
+        self.pending = None;
+        self.pending_payload.clear();
+    }
+
+    fn text_response(text: &str) -> Vec<u8> {
+        std::iter::once(ESC).chain(text.bytes()).collect()
+    }
+
+    fn complete_pending(&mut self, operation: PendingOperation) {
+        match operation {
+            PendingOperation::ReadIso => {
+                let mut status = STATUS_OK;
+                let mut response = vec![ESC, 0x73];
+                for (index, track) in self.card.tracks.iter().enumerate() {
+                    response.extend([ESC, index as u8 + 1]);
+                    match Self::decode(track, index + 1, self.bpc[index]) {
+                        Some(text) => response.extend(text.bytes()),
+                        None => status = 0x31,
+                    }
+                }
+                response.extend([0x3f, FS, ESC, status]);
+                self.queue_response(&response);
+            }
+            PendingOperation::ReadRaw => {
+                let mut response = vec![ESC, 0x73];
+                for (index, track) in self.card.tracks.iter().enumerate() {
+                    response.extend([ESC, index as u8 + 1, track.len() as u8]);
+                    response.extend(track);
+                }
+                response.extend([0x3f, FS, ESC, STATUS_OK]);
+                self.queue_response(&response);
+            }
+            PendingOperation::WriteIso => {
+                let status = match Self::parse_data_block(&self.pending_payload) {
+                    Some(tracks) => {
+                        for (index, track) in tracks.into_iter().enumerate() {
+                            match track {
+                                Some(track) if track.is_empty() => self.card.tracks[index] = vec![],
+                                Some(track) => {
+                                    self.card.tracks[index] = VirtualCard::encode(&track, index + 1)
+                                }
+                                None => {}
+                            }
+                        }
+                        STATUS_OK
+                    }
+                    None => 0x32,
+                };
+                self.queue_response(&[ESC, status]);
+            }
+            PendingOperation::WriteRaw => {
+                let status = match Self::parse_raw_data_block(&self.pending_payload) {
+                    Some(tracks) => {
+                        for (index, track) in tracks.into_iter().enumerate() {
+                            if let Some(track) = track {
+                                self.card.tracks[index] = track;
+                            }
+                        }
+                        STATUS_OK
+                    }
+                    None => 0x32,
+                };
+                self.queue_response(&[ESC, status]);
+            }
+            PendingOperation::SensorTest => self.queue_response(&[ESC, STATUS_OK]),
+            PendingOperation::Erase(select_byte) => {
+                let status = self.erase(select_byte);
+                self.queue_response(&[ESC, status]);
+            }
+        }
+    }
+
+    fn erase(&mut self, select_byte: u8) -> u8 {
+        match TrackSelection::from_select_byte(select_byte) {
+            Ok(selection) => {
+                for (index, selected) in [selection.track1, selection.track2, selection.track3]
+                    .iter()
+                    .enumerate()
+                {
+                    if *selected {
+                        self.card.tracks[index].clear();
+                    }
+                }
+                STATUS_OK
+            }
+            Err(_) => 0x41,
+        }
+    }
+
+    /// Decodes track like the device does in ISO mode, `None` if the track can't be read
+    fn decode(track: &[u8], track_number: usize, bits_per_character: u8) -> Option<String> {
+        if track.is_empty() {
+            return Some(String::new());
+        }
+        match decode_track(track, track_number, bits_per_character) {
+            Ok(text) if text.len() > 1 && text.ends_with('?') => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Parses raw write data block. Tracks which are not in the block are left as they are,
+    /// tracks with zero length are erased
+    fn parse_raw_data_block(block: &[u8]) -> Option<[Option<Vec<u8>>; 3]> {
+        if block.get(0..2)? != [ESC, 0x73] {
+            return None;
+        }
+        let mut tracks: [Option<Vec<u8>>; 3] = [None, None, None];
+        let mut index = 2;
+        for (track_index, track) in tracks.iter_mut().enumerate() {
+            if block.get(index..index + 2)? != [ESC, track_index as u8 + 1] {
+                continue;
+            }
+            let length = *block.get(index + 2)? as usize;
+            *track = Some(block.get(index + 3..index + 3 + length)?.to_vec());
+            index += 3 + length;
+        }
+        if block.get(index..)? != [0x3f, FS] {
+            return None;
+        }
+        Some(tracks)
+    }
+
+    /// Parses write data block and adds sentinels to each track, like the device does.
+    /// Tracks which are not in the block are left as they are, tracks without data are erased
+    fn parse_data_block(block: &[u8]) -> Option<[Option<Vec<u8>>; 3]> {
+        if block.len() < 4 || block[0..2] != [ESC, 0x73] || block[block.len() - 2..] != [0x3f, FS] {
+            return None;
+        }
+        let card_data = &block[2..block.len() - 2];
+        let mut tracks: [Option<Vec<u8>>; 3] = [None, None, None];
+        let mut current_track: Option<usize> = None;
+        let mut index = 0;
+
+        while index < card_data.len() {
+            if card_data[index] == ESC && index + 1 < card_data.len() {
+                if let 1..=3 = card_data[index + 1] {
+                    current_track = Some(card_data[index + 1] as usize - 1);
+                    tracks[current_track?] = Some(vec![]);
+                    index += 2;
+                    continue;
+                }
+            }
+            tracks[current_track?].as_mut()?.push(card_data[index]);
+            index += 1;
+        }
+
+        let [track1, track2, track3] = tracks;
+        Some([
+            track1.map(|data| Self::with_sentinels(b'%', data)),
+            track2.map(|data| Self::with_sentinels(b';', data)),
+            track3.map(|data| Self::with_sentinels(b';', data)),
+        ])
+    }
+
+    /// Empty track stays empty, it's written as a blank track
+    fn with_sentinels(start_sentinel: u8, data: Vec<u8>) -> Vec<u8> {
+        if data.is_empty() {
+            return data;
+        }
+        std::iter::once(start_sentinel)
+            .chain(data)
+            .chain(std::iter::once(b'?'))
+            .collect()
+    }
+
+    /// Splits response into 64 byte interrupt reports, first byte of each report tells
+    /// if it's the first and/or the last report and how many bytes of data it has
+    fn queue_response(&mut self, response: &[u8]) {
+        let chunks: Vec<&[u8]> = response.chunks(63).collect();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut report = [0; 64];
+            let mut header = chunk.len() as u8;
+            if index == 0 {
+                header |= 0x80;
+            }
+            if index == chunks.len() - 1 {
+                header |= 0x40;
+            }
+            report[0] = header;
+            report[1..1 + chunk.len()].copy_from_slice(chunk);
+            self.responses.push_back(report);
+        }
+    }
+}
+
+impl Transport for Emulator {
+    fn send_control_chunk(
+        &mut self,
+        _endpoint: u8,
+        chunk: &[u8],
+        _timeout: &Duration,
+    ) -> Result<(), MsrxToolError> {
+        let Some((&header, data)) = chunk.split_first() else {
+            return Ok(());
+        };
+        let length = (header & !(0x80 | 0x40)) as usize;
+        self.incoming.extend(&data[..length.min(data.len())]);
+
+        if header & 0x40 != 0 {
+            let packets = std::mem::take(&mut self.incoming);
+            self.handle_command(packets);
+        }
+        Ok(())
+    }
+
+    fn read_interrupt(
+        &mut self,
+        _endpoint: u8,
+        _timeout: &Duration,
+    ) -> Result<[u8; 64], MsrxToolError> {
+        if self.responses.is_empty() {
+            if let Some(operation) = self.pending {
+                let swipe = self.swipes.pop_front().unwrap_or(if self.auto_swipe {
+                    Swipe::Card
+                } else {
+                    Swipe::NoCard
+                });
+                if swipe == Swipe::Card {
+                    self.pending = None;
+                    self.complete_pending(operation);
+                }
+            }
+        }
+
+        self.responses
+            .pop_front()
+            .ok_or(MsrxToolError::DeviceError(rusb::Error::Timeout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeviceConfig;
+    use crate::data_format::DataFormat;
+    use crate::led::Led;
+    use crate::msrx::MsrxDevice;
+    use crate::output::{self, OutputFormat};
+    use crate::redaction::Redaction;
+    use crate::self_test::SelfTest;
+    use crate::track_action::TrackAction;
+    use crate::track_status::TrackStatus;
+    use crate::tracks_data::TracksData;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn setup(emulator: Emulator) -> Result<MsrxDevice<Emulator>, MsrxToolError> {
+        let mut device = MsrxDevice::with_transport(emulator, DeviceConfig::msrx6());
+        device.setup_device()?;
+        Ok(device)
+    }
+
+    #[test]
+    fn test_setup_device_configures_emulator() -> Result<(), MsrxToolError> {
+        let device = setup(Emulator::default())?;
+
+        assert_eq!(device.transport.bpc, [7, 5, 5]);
+        assert_eq!(device.transport.bpi, vec![0xa1, 0xc0, 0xd2]);
+        assert!(device.transport.is_hi_co);
+        assert_eq!(device.transport.leading_zeros, [61, 22]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_firmware_and_model() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::default())?;
+
+        assert_eq!(device.get_firmware_version()?, "REVT3.12");
+        assert_eq!(device.get_model()?, "3S");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_card_spanning_multiple_reports() -> Result<(), MsrxToolError> {
+        let track1 =
+            "%ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMN?";
+        let track2 = ";0987654321098765432109876543210987654?";
+        let card = VirtualCard::from_tracks(track1, track2, ";12345?");
+        let mut device = setup(Emulator::with_card(card))?;
+
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(tracks_data.track1.to_string()?, track1);
+        assert_eq!(tracks_data.track2.to_string()?, track2);
+        assert_eq!(tracks_data.track3.to_string()?, ";12345?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_and_read_back() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        let data = TracksData::from_str("%HELLO WORLD?_;1234?_;5678?", &'_')?;
+
+        assert!(device.write_tracks(&data, &Duration::from_secs(1))?);
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(tracks_data.track1.to_string()?, "%HELLO WORLD?");
+        assert_eq!(tracks_data.track2.to_string()?, ";1234?");
+        assert_eq!(tracks_data.track3.to_string()?, ";5678?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_combined_format_round_trip() -> Result<(), MsrxToolError> {
+        let timeout = Duration::from_secs(1);
+        let cards = [
+            ("%A_B\\C?", ";1234=5678?", ";5678?"),
+            ("%__?", "", ";1?"),
+            ("%\\_\\|^?", ";=?", ""),
+            ("", "", ""),
+        ];
+
+        for separator in ['_', '|', '^', '='] {
+            for (track1, track2, track3) in cards {
+                for data_format in [DataFormat::Iso, DataFormat::Raw] {
+                    let card = VirtualCard::from_tracks(track1, track2, track3);
+                    let mut reader = setup(Emulator::with_card(card))?;
+                    let read = reader.read_tracks(&data_format, &timeout)?;
+                    let text = output::format(
+                        &read,
+                        &OutputFormat::Combined,
+                        &Some(separator),
+                        None,
+                        None,
+                        None,
+                        &Redaction::clear(),
+                    );
+
+                    let parsed = match data_format {
+                        DataFormat::Iso => TracksData::from_str(&text, &separator)?,
+                        DataFormat::Raw => TracksData::from_raw_str(&text, &separator)?,
+                    };
+                    let mut writer = setup(Emulator::with_card(VirtualCard::default()))?;
+                    assert!(writer.write_tracks(&parsed, &timeout)?);
+
+                    assert_eq!(writer.transport.card.tracks, reader.transport.card.tracks);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_track_actions() -> Result<(), MsrxToolError> {
+        let timeout = Duration::from_secs(1);
+        let original = VirtualCard::from_tracks("%OLD?", ";111?", ";222?");
+
+        for data_format in [DataFormat::Iso, DataFormat::Raw] {
+            let mut device = setup(Emulator::with_card(original.clone()))?;
+            let mut data = match data_format {
+                DataFormat::Iso => TracksData::from_str("%NEW?_;333?_;444?", &'_')?,
+                DataFormat::Raw => TracksData::from_raw_str(
+                    &format!(
+                        "_{}_{}",
+                        hex::encode(VirtualCard::from_tracks("", ";333?", "").tracks[1].clone()),
+                        hex::encode(VirtualCard::from_tracks("", "", ";444?").tracks[2].clone())
+                    ),
+                    &'_',
+                )?,
+            };
+            data.track1.action = TrackAction::Leave;
+            data.track2.action = TrackAction::Erase;
+            data.track3.action = TrackAction::Write;
+
+            assert!(device.write_tracks(&data, &timeout)?);
+            let tracks_data = device.read_tracks(&DataFormat::Iso, &timeout)?;
+
+            assert_eq!(tracks_data.track1.to_string()?, "%OLD?");
+            assert_eq!(tracks_data.track2.to_string()?, "");
+            assert_eq!(tracks_data.track3.to_string()?, ";444?");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_and_verify() -> Result<(), MsrxToolError> {
+        let timeout = Duration::from_secs(1);
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        let data = TracksData::from_str("%HELLO?_;1234?", &'_')?;
+
+        assert!(device.write_tracks(&data, &timeout)?);
+        assert!(device.verify_tracks(&data, &timeout)?.is_empty());
+
+        device.transport.card = VirtualCard::from_tracks("%HELLO?", ";9999?", ";5?");
+        let mismatches = device.verify_tracks(&data, &timeout)?;
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].track, 2);
+        assert_eq!(mismatches[0].actual, ";9999?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_clone_to_cards_with_data() -> Result<(), MsrxToolError> {
+        let timeout = Duration::from_secs(1);
+        for data_format in [DataFormat::Iso, DataFormat::Raw] {
+            let source = VirtualCard::from_tracks("%HELLO?", ";1234?", "");
+            let mut device = setup(Emulator::with_card(source.clone()))?;
+            let tracks_data = device.read_tracks(&data_format, &timeout)?;
+
+            for copy in [
+                VirtualCard::default(),
+                VirtualCard::from_tracks("%OLD?", ";9?", ";5?"),
+            ] {
+                device.transport.card = copy;
+                assert!(device.write_tracks(&tracks_data, &timeout)?);
+                assert!(device.verify_tracks(&tracks_data, &timeout)?.is_empty());
+                assert_eq!(device.transport.card, source);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_cards_until_count() -> Result<(), MsrxToolError> {
+        let mut emulator = Emulator::with_card(VirtualCard::from_tracks("%A?", ";1?", ""));
+        for swipe in [
+            Swipe::NoCard,
+            Swipe::Card,
+            Swipe::NoCard,
+            Swipe::NoCard,
+            Swipe::Card,
+        ] {
+            emulator.queue_swipe(swipe);
+        }
+        let mut device = setup(emulator)?;
+        let mut read = vec![];
+
+        let cards = device.read_cards(
+            &DataFormat::Iso,
+            &Duration::from_secs(1),
+            Some(3),
+            &AtomicBool::new(true),
+            |tracks_data| {
+                read.push(tracks_data);
+                Ok(())
+            },
+        )?;
+
+        assert_eq!(cards, 3);
+        assert_eq!(read.len(), 3);
+        assert!(read
+            .iter()
+            .all(|tracks_data| tracks_data.status == TrackStatus::Ok));
+        assert_eq!(read[2].track2.data, b";1?".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_cards_timeout_does_not_reinitialize() -> Result<(), MsrxToolError> {
+        let mut emulator = Emulator::with_card(VirtualCard::from_tracks("", ";1?", ""));
+        emulator.queue_swipe(Swipe::NoCard);
+        emulator.queue_swipe(Swipe::NoCard);
+        let mut device = setup(emulator)?;
+        device.led_feedback = true;
+        // Every device initialization sets bits per inch of all tracks again
+        let bpi_after_setup = device.transport.bpi.len();
+
+        let cards = device.read_cards(
+            &DataFormat::Iso,
+            &Duration::from_secs(1),
+            Some(1),
+            &AtomicBool::new(true),
+            |_| Ok(()),
+        )?;
+
+        assert_eq!(cards, 1);
+        assert_eq!(device.transport.bpi.len(), bpi_after_setup);
+        assert_eq!(device.transport.led_history, vec![Led::Yellow, Led::Green]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_cards_stops_when_not_running() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        let running = AtomicBool::new(true);
+
+        let cards = device.read_cards(
+            &DataFormat::Iso,
+            &Duration::from_secs(1),
+            None,
+            &running,
+            |_| {
+                running.store(false, Ordering::SeqCst);
+                Ok(())
+            },
+        )?;
+
+        assert_eq!(cards, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_raw_and_decode() -> Result<(), MsrxToolError> {
+        let card = VirtualCard::from_tracks("%HELLO WORLD?", ";1234=5678?", "");
+        let mut device = setup(Emulator::with_card(card))?;
+
+        let tracks_data = device.read_tracks(&DataFormat::Raw, &Duration::from_secs(1))?;
+        let decoded = tracks_data.decode_raw(&[7, 5, 5])?;
+
+        assert!(tracks_data.track3.data.is_empty());
+        assert_eq!(decoded.track1.to_string()?, "%HELLO WORLD?");
+        assert_eq!(decoded.track2.to_string()?, ";1234=5678?");
+        assert_eq!(decoded.track3.to_string()?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_raw_and_read_raw_back() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::from_tracks(
+            "%A?", ";1?", ";2?",
+        )))?;
+        let data = TracksData::from_raw_str("00ff1b3f1c__", &'_')?;
+
+        assert!(device.write_tracks(&data, &Duration::from_secs(1))?);
+        let tracks_data = device.read_tracks(&DataFormat::Raw, &Duration::from_secs(1))?;
+
+        assert_eq!(tracks_data.track1.data, vec![0x00, 0xff, 0x1b, 0x3f, 0x1c]);
+        // Tracks without data are not written
+        assert_eq!(
+            tracks_data.track3.data,
+            VirtualCard::from_tracks("", "", ";2?").tracks[2]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_raw_and_read_iso() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        // ";1?" on track 2, swiped backwards
+        let data = TracksData::from_raw_str("_afc2b000_", &'_')?;
+
+        assert!(device.write_tracks(&data, &Duration::from_secs(1))?);
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(tracks_data.track2.to_string()?, ";1?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_encoded_raw_with_lrc() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        let data = TracksData::from_str("%HELLO?_;1234=5678?", &'_')?.encode_raw()?;
+
+        assert!(device.write_tracks(&data, &Duration::from_secs(1))?);
+        let raw = device.read_tracks(&DataFormat::Raw, &Duration::from_secs(1))?;
+        let iso = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(raw.check_lrc(&[7, 5, 5]), [Ok(()), Ok(()), Ok(())]);
+        assert_eq!(raw.decode_raw(&[7, 5, 5])?.track1.to_string()?, "%HELLO?");
+        assert_eq!(iso.track2.to_string()?, ";1234=5678?");
+        assert_eq!(
+            data.encode_raw().unwrap_err(),
+            MsrxToolError::UnsupportedDataFormat
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_read_reports_lrc_per_track() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+        // Track 2 ";1?" without LRC, track 3 ";1?" with wrong LRC "0"
+        let data = TracksData::from_raw_str("_d43e_d43e10_", &'_')?;
+
+        assert!(device.write_tracks(&data, &Duration::from_secs(1))?);
+        let raw = device.read_tracks(&DataFormat::Raw, &Duration::from_secs(1))?;
+
+        assert_eq!(
+            raw.check_lrc(&[7, 5, 5]),
+            [
+                Ok(()),
+                Err(MsrxToolError::LrcMissing(2)),
+                Err(MsrxToolError::LrcMismatch(3, '5', '0'))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_erase_selected_tracks() -> Result<(), MsrxToolError> {
+        let card = VirtualCard::from_tracks("%A?", ";1?", ";2?");
+        let mut device = setup(Emulator::with_card(card))?;
+        let selection = TrackSelection::from_track_numbers(&[1, 3])?;
+
+        let status = device.erase_tracks(&selection, &Duration::from_secs(1))?;
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(status, TrackStatus::Ok);
+        assert_eq!(tracks_data.track1.to_string()?, "");
+        assert_eq!(tracks_data.track2.to_string()?, ";1?");
+        assert_eq!(tracks_data.track3.to_string()?, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_erase_without_swipe_is_card_not_swiped() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::default())?;
+
+        let result = device.erase_tracks(&TrackSelection::all(), &Duration::from_secs(1));
+
+        assert_eq!(result.unwrap_err(), MsrxToolError::CardNotSwiped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_tests() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::with_card(VirtualCard::default()))?;
+
+        for test in SelfTest::all() {
+            assert!(device.run_self_test(&test, &Duration::from_secs(1))?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_test_failures() -> Result<(), MsrxToolError> {
+        let emulator = Emulator {
+            ram_failure: true,
+            ..Default::default()
+        };
+        let mut device = setup(emulator)?;
+
+        let ram = device.run_self_test(&SelfTest::Ram, &Duration::from_secs(1));
+        let sensor = device.run_self_test(&SelfTest::Sensor, &Duration::from_secs(1));
+
+        assert_eq!(ram, Ok(false));
+        assert_eq!(sensor, Err(MsrxToolError::CardNotSwiped));
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_led() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::default())?;
+
+        device.set_led(&Led::Green)?;
+        device.set_led(&Led::Off)?;
+
+        assert_eq!(device.transport.led_history, vec![Led::Green, Led::Off]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_led_feedback() -> Result<(), MsrxToolError> {
+        let mut emulator = Emulator::with_card(VirtualCard::from_tracks("%A?", ";1?", ";2?"));
+        emulator.queue_swipe(Swipe::Card);
+        emulator.queue_swipe(Swipe::NoCard);
+        let mut device = setup(emulator)?;
+        device.led_feedback = true;
+
+        let _ = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+        let _ = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1));
+
+        assert_eq!(
+            device.transport.led_history,
+            vec![Led::Yellow, Led::Green, Led::Yellow, Led::Red]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_without_swipe_is_card_not_swiped() -> Result<(), MsrxToolError> {
+        let mut emulator = Emulator::with_card(VirtualCard::from_tracks("%A?", ";1?", ";2?"));
+        emulator.queue_swipe(Swipe::NoCard);
+        let mut device = setup(emulator)?;
+
+        let result = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1));
+
+        assert_eq!(result.unwrap_err(), MsrxToolError::CardNotSwiped);
+        // Device is usable again after the timeout
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+        assert_eq!(tracks_data.track1.to_string()?, "%A?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_without_swipe_is_card_not_swiped() -> Result<(), MsrxToolError> {
+        let mut device = setup(Emulator::default())?;
+        let data = TracksData::from_str("%A?_;1?_;2?", &'_')?;
+
+        let result = device.write_tracks(&data, &Duration::from_secs(1));
+
+        assert_eq!(result.unwrap_err(), MsrxToolError::CardNotSwiped);
+        assert_eq!(device.transport.card, VirtualCard::default());
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+/// Transports
+/// Module defines how `MsrxDevice` talks to the hardware. A transport only knows how to send
+/// framed control chunks and how to read 64 byte interrupt reports, everything else
+/// (command framing, parsing responses) is done on top of it.
+pub mod emulator;
+pub mod rusb_transport;
+pub mod session;
+use crate::msrx_tool_error::MsrxToolError;
+use std::time::Duration;
+
+pub trait Transport {
+    /// Sends one already framed chunk (header byte + max 63 bytes of payload) to the device
+    fn send_control_chunk(
+        &mut self,
+        endpoint: u8,
+        chunk: &[u8],
+        timeout: &Duration,
+    ) -> Result<(), MsrxToolError>;
+
+    /// Reads one 64 byte interrupt report from the device
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        timeout: &Duration,
+    ) -> Result<[u8; 64], MsrxToolError>;
+
+    fn set_auto_detach_kernel_driver(&mut self, _enabled: bool) -> Result<(), MsrxToolError> {
+        Ok(())
+    }
+
+    fn claim_interface(&mut self, _interface: u8) -> Result<(), MsrxToolError> {
+        Ok(())
+    }
+
+    fn release_interface(&mut self, _interface: u8) -> Result<(), MsrxToolError> {
+        Ok(())
+    }
+
+    fn reset_device(&mut self) -> Result<(), MsrxToolError> {
+        Ok(())
+    }
+}
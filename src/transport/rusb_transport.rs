@@ -0,0 +1,68 @@
+use crate::msrx_tool_error::MsrxToolError;
+use crate::transport::Transport;
+use rusb::{Context, DeviceHandle, UsbContext};
+use std::time::Duration;
+
+/// Transport for devices connected over USB HID, using libusb through rusb
+#[derive(Debug)]
+pub struct RusbTransport {
+    pub device_handle: DeviceHandle<Context>,
+}
+
+impl RusbTransport {
+    pub fn open(vendor_id: u16, product_id: u16) -> Result<Self, MsrxToolError> {
+        // Initialize a USB context
+        let context = Context::new().expect("Failed to initialize USB context");
+
+        match context.open_device_with_vid_pid(vendor_id, product_id) {
+            Some(device_handle) => Ok(RusbTransport { device_handle }),
+            None => Err(MsrxToolError::DeviceNotFound),
+        }
+    }
+}
+
+impl Transport for RusbTransport {
+    fn send_control_chunk(
+        &mut self,
+        endpoint: u8,
+        chunk: &[u8],
+        timeout: &Duration,
+    ) -> Result<(), MsrxToolError> {
+        let _ =
+            self.device_handle
+                .write_control(0x21, 9, 0x0300, endpoint as u16, chunk, *timeout)?;
+        Ok(())
+    }
+
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        timeout: &Duration,
+    ) -> Result<[u8; 64], MsrxToolError> {
+        let mut raw_data: [u8; 64] = [0; 64];
+        let _ = self
+            .device_handle
+            .read_interrupt(endpoint, &mut raw_data, *timeout)?;
+        Ok(raw_data)
+    }
+
+    fn set_auto_detach_kernel_driver(&mut self, enabled: bool) -> Result<(), MsrxToolError> {
+        self.device_handle.set_auto_detach_kernel_driver(enabled)?;
+        Ok(())
+    }
+
+    fn claim_interface(&mut self, interface: u8) -> Result<(), MsrxToolError> {
+        self.device_handle.claim_interface(interface)?;
+        Ok(())
+    }
+
+    fn release_interface(&mut self, interface: u8) -> Result<(), MsrxToolError> {
+        self.device_handle.release_interface(interface)?;
+        Ok(())
+    }
+
+    fn reset_device(&mut self) -> Result<(), MsrxToolError> {
+        self.device_handle.reset()?;
+        Ok(())
+    }
+}
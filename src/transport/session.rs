@@ -0,0 +1,329 @@
+use crate::msrx_tool_error::MsrxToolError;
+use crate::to_hex::ToHex;
+use crate::transport::Transport;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Session file contains one event per line:
+///
+///   <milliseconds since start> > <hex bytes>    chunk sent to the device
+///   <milliseconds since start> < <hex bytes>    interrupt report received from the device
+///   <milliseconds since start> < timeout        reading timed out
+///   <milliseconds since start> < error          reading failed with some other error
+///
+/// Lines starting with `#` are comments. Card data is recorded as it is, without redaction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    Sent(Vec<u8>),
+    Received(Vec<u8>),
+    Timeout,
+    Error,
+}
+
+impl SessionEvent {
+    fn to_line(&self, elapsed: &Duration) -> String {
+        let event = match self {
+            SessionEvent::Sent(data) => format!("> {}", data.to_hex()),
+            SessionEvent::Received(data) => format!("< {}", data.to_hex()),
+            SessionEvent::Timeout => "< timeout".to_string(),
+            SessionEvent::Error => "< error".to_string(),
+        };
+        format!("{} {}", elapsed.as_millis(), event)
+    }
+
+    fn from_line(line: &str, line_number: usize) -> Result<Self, MsrxToolError> {
+        let invalid = || MsrxToolError::InvalidSessionFile(line_number);
+        let mut parts = line.splitn(3, ' ');
+        let _elapsed: u128 = parts
+            .next()
+            .and_then(|elapsed| elapsed.parse().ok())
+            .ok_or_else(invalid)?;
+        let direction = parts.next().ok_or_else(invalid)?;
+        let payload = parts.next().unwrap_or("").trim();
+
+        match (direction, payload) {
+            ("<", "timeout") => Ok(SessionEvent::Timeout),
+            ("<", "error") => Ok(SessionEvent::Error),
+            (">", hex_bytes) => Ok(SessionEvent::Sent(
+                Self::decode(hex_bytes).ok_or_else(invalid)?,
+            )),
+            ("<", hex_bytes) => Ok(SessionEvent::Received(
+                Self::decode(hex_bytes).ok_or_else(invalid)?,
+            )),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn decode(hex_bytes: &str) -> Option<Vec<u8>> {
+        hex::decode(hex_bytes.replace(' ', "")).ok()
+    }
+}
+
+/// Transport which passes everything through to another transport and logs
+/// the traffic to a session file
+#[derive(Debug)]
+pub struct RecordingTransport<T: Transport> {
+    pub inner: T,
+    file: File,
+    started: Instant,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn create(inner: T, path: &Path) -> Result<Self, MsrxToolError> {
+        let mut file =
+            File::create(path).map_err(|e| MsrxToolError::SessionFileError(e.to_string()))?;
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        writeln!(
+            file,
+            "# msrx-tool session, started at {} (unix time)",
+            started_at.as_secs()
+        )
+        .map_err(|e| MsrxToolError::SessionFileError(e.to_string()))?;
+
+        Ok(RecordingTransport {
+            inner,
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    fn record(&mut self, event: SessionEvent) -> Result<(), MsrxToolError> {
+        // Written line by line so the file is usable even if the tool crashes
+        writeln!(self.file, "{}", event.to_line(&self.started.elapsed()))
+            .map_err(|e| MsrxToolError::SessionFileError(e.to_string()))
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send_control_chunk(
+        &mut self,
+        endpoint: u8,
+        chunk: &[u8],
+        timeout: &Duration,
+    ) -> Result<(), MsrxToolError> {
+        self.record(SessionEvent::Sent(chunk.to_vec()))?;
+        self.inner.send_control_chunk(endpoint, chunk, timeout)
+    }
+
+    fn read_interrupt(
+        &mut self,
+        endpoint: u8,
+        timeout: &Duration,
+    ) -> Result<[u8; 64], MsrxToolError> {
+        let result = self.inner.read_interrupt(endpoint, timeout);
+        match &result {
+            Ok(report) => self.record(SessionEvent::Received(report.to_vec()))?,
+            Err(MsrxToolError::DeviceError(rusb::Error::Timeout)) => {
+                self.record(SessionEvent::Timeout)?
+            }
+            Err(_) => self.record(SessionEvent::Error)?,
+        }
+        result
+    }
+
+    fn set_auto_detach_kernel_driver(&mut self, enabled: bool) -> Result<(), MsrxToolError> {
+        self.inner.set_auto_detach_kernel_driver(enabled)
+    }
+
+    fn claim_interface(&mut self, interface: u8) -> Result<(), MsrxToolError> {
+        self.inner.claim_interface(interface)
+    }
+
+    fn release_interface(&mut self, interface: u8) -> Result<(), MsrxToolError> {
+        self.inner.release_interface(interface)
+    }
+
+    fn reset_device(&mut self) -> Result<(), MsrxToolError> {
+        self.inner.reset_device()
+    }
+}
+
+/// Transport which plays back a recorded session. Sent chunks are compared against
+/// the recording so a replay fails loudly if the tool talks differently to the device
+/// than it did when the session was recorded.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    events: VecDeque<(usize, SessionEvent)>,
+}
+
+impl ReplayTransport {
+    pub fn from_file(path: &Path) -> Result<Self, MsrxToolError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| MsrxToolError::SessionFileError(e.to_string()))?;
+        Self::from_session(&content)
+    }
+
+    pub fn from_session(content: &str) -> Result<Self, MsrxToolError> {
+        let mut events = VecDeque::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            events.push_back((index + 1, SessionEvent::from_line(line, index + 1)?));
+        }
+        Ok(ReplayTransport { events })
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_control_chunk(
+        &mut self,
+        _endpoint: u8,
+        chunk: &[u8],
+        _timeout: &Duration,
+    ) -> Result<(), MsrxToolError> {
+        match self.events.pop_front() {
+            Some((_, SessionEvent::Sent(data))) if data == chunk => Ok(()),
+            Some((line_number, _)) => Err(MsrxToolError::ReplayMismatch(line_number)),
+            None => Err(MsrxToolError::ReplaySessionEnded),
+        }
+    }
+
+    fn read_interrupt(
+        &mut self,
+        _endpoint: u8,
+        _timeout: &Duration,
+    ) -> Result<[u8; 64], MsrxToolError> {
+        match self.events.pop_front() {
+            Some((line_number, SessionEvent::Received(data))) => {
+                // Trailing zeros can be left out of the session file
+                if data.len() > 64 {
+                    return Err(MsrxToolError::InvalidSessionFile(line_number));
+                }
+                let mut report = [0; 64];
+                report[..data.len()].copy_from_slice(&data);
+                Ok(report)
+            }
+            Some((_, SessionEvent::Timeout)) => {
+                Err(MsrxToolError::DeviceError(rusb::Error::Timeout))
+            }
+            Some((_, SessionEvent::Error)) => Err(MsrxToolError::Unknown),
+            Some((line_number, SessionEvent::Sent(_))) => {
+                Err(MsrxToolError::ReplayMismatch(line_number))
+            }
+            None => Err(MsrxToolError::ReplaySessionEnded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeviceConfig;
+    use crate::data_format::DataFormat;
+    use crate::msrx::MsrxDevice;
+    use crate::transport::emulator::{Emulator, Swipe, VirtualCard};
+
+    fn setup_session() -> String {
+        [
+            "# setup",
+            "0 > c5 1b 6f 07 05 05",
+            "1 < c5 1b 30 07 05 05",
+            "2 > c2 1b 78",
+            "3 < c2 1b 30",
+            "4 > c3 1b 62 a1",
+            "5 < c2 1b 30",
+            "6 > c3 1b 62 c0",
+            "7 < c2 1b 30",
+            "8 > c3 1b 62 d2",
+            "9 < c2 1b 30",
+            "10 > c4 1b 7a 3d 16",
+            "11 < c2 1b 30",
+        ]
+        .join("\n")
+    }
+
+    #[test]
+    fn test_event_line_round_trip() -> Result<(), MsrxToolError> {
+        let events = [
+            SessionEvent::Sent(vec![0xc2, 0x1b, 0x72]),
+            SessionEvent::Received(vec![0xc2, 0x1b, 0x30]),
+            SessionEvent::Timeout,
+            SessionEvent::Error,
+        ];
+
+        for (index, event) in events.iter().enumerate() {
+            let line = event.to_line(&Duration::from_millis(1500));
+            assert_eq!(&SessionEvent::from_line(&line, index)?, event);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        let result = ReplayTransport::from_session("0 > c2 1b 72\nfoo bar\n");
+
+        assert_eq!(result.unwrap_err(), MsrxToolError::InvalidSessionFile(2));
+    }
+
+    #[test]
+    fn test_replay_read_spanning_multiple_reports() -> Result<(), MsrxToolError> {
+        let session = [
+            setup_session(),
+            "12 > c2 1b 72".to_string(),
+            "13 < bf 1b 73 1b 01 25 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f 50 51 52 53 54 55 31 32 33 34 35 36 37 38 39 30 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f 50 51 52 53 54 55 31 32 33 34 35 36".to_string(),
+            "14 < 3f 37 38 39 30 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 3f 1b 02 3b 30 39 38 37 36 35 34 33 32 31 30 39 38 37 36 35 34 33 32 31 30 39 38 37 36 35 34 33 32 31 30 39 38 37 36 35 34 3f 1b 03 3b".to_string(),
+            "15 < 4a 31 32 33 34 35 3f 3f 1c 1b 30".to_string(),
+        ]
+        .join("\n");
+        let transport = ReplayTransport::from_session(&session)?;
+        let mut device = MsrxDevice::with_transport(transport, DeviceConfig::msrx6());
+
+        device.setup_device()?;
+        let tracks_data = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        assert_eq!(
+            tracks_data.track1.to_string()?,
+            "%ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMNOPQRSTU1234567890ABCDEFGHIJKLMN?"
+        );
+        assert_eq!(
+            tracks_data.track2.to_string()?,
+            ";0987654321098765432109876543210987654?"
+        );
+        assert_eq!(tracks_data.track3.to_string()?, ";12345?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_detects_different_command() -> Result<(), MsrxToolError> {
+        let transport = ReplayTransport::from_session("0 > c2 1b 76\n")?;
+        let mut device = MsrxDevice::with_transport(transport, DeviceConfig::msrx6());
+
+        let result = device.get_model();
+
+        assert_eq!(result.unwrap_err(), MsrxToolError::ReplayMismatch(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_and_replay_emulator_session() -> Result<(), MsrxToolError> {
+        let path = std::env::temp_dir().join("msrx-tool-test-session.txt");
+        let mut emulator = Emulator::with_card(VirtualCard::from_tracks("%A?", ";1?", ";2?"));
+        emulator.queue_swipe(Swipe::NoCard);
+        let recording = RecordingTransport::create(emulator, &path)?;
+        let mut device = MsrxDevice::with_transport(recording, DeviceConfig::msrx6());
+        device.setup_device()?;
+        let recorded_timeout = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1));
+        let recorded = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+
+        let mut device =
+            MsrxDevice::with_transport(ReplayTransport::from_file(&path)?, DeviceConfig::msrx6());
+        device.setup_device()?;
+        let replayed_timeout = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1));
+        let replayed = device.read_tracks(&DataFormat::Iso, &Duration::from_secs(1))?;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(recorded_timeout.unwrap_err(), MsrxToolError::CardNotSwiped);
+        assert_eq!(replayed_timeout.unwrap_err(), MsrxToolError::CardNotSwiped);
+        assert_eq!(recorded.track1.to_string()?, replayed.track1.to_string()?);
+        assert_eq!(recorded.track2.to_string()?, replayed.track2.to_string()?);
+        assert_eq!(recorded.track3.to_string()?, replayed.track3.to_string()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::data_format::DataFormat;
+use crate::track_action::TrackAction;
+use crate::track_data::TrackData;
+use crate::tracks_data::TracksData;
+
+/// Track which was read back with different content than was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackMismatch {
+    pub track: u8,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for TrackMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Track {}: expected \"{}\", read \"{}\"",
+            self.track, self.expected, self.actual
+        )
+    }
+}
+
+/// Compares written tracks to tracks read back from the card. Tracks which were left as is
+/// are not compared and erased tracks must be empty. Raw tracks are compared without leading
+/// and trailing zero bytes, because the device pads the stripe with zeros.
+pub fn compare(written: &TracksData, read: &TracksData) -> Vec<TrackMismatch> {
+    [
+        (&written.track1, &read.track1),
+        (&written.track2, &read.track2),
+        (&written.track3, &read.track3),
+    ]
+    .iter()
+    .enumerate()
+    .filter_map(|(index, (written, read))| {
+        let expected: &[u8] = match written.action {
+            TrackAction::Leave => return None,
+            TrackAction::Erase => &[],
+            TrackAction::Write => &written.data,
+        };
+        let expected = content(expected, written.format);
+        let actual = content(&read.data, read.format);
+        if expected == actual {
+            None
+        } else {
+            Some(TrackMismatch {
+                track: index as u8 + 1,
+                expected: to_string(expected, written),
+                actual: to_string(actual, read),
+            })
+        }
+    })
+    .collect()
+}
+
+fn content(data: &[u8], format: DataFormat) -> &[u8] {
+    match format {
+        DataFormat::Iso => data,
+        DataFormat::Raw => {
+            let start = data
+                .iter()
+                .position(|&byte| byte != 0)
+                .unwrap_or(data.len());
+            let end = data
+                .iter()
+                .rposition(|&byte| byte != 0)
+                .map_or(start, |end| end + 1);
+            &data[start..end]
+        }
+    }
+}
+
+fn to_string(data: &[u8], track: &TrackData) -> String {
+    match track.format {
+        DataFormat::Iso => String::from_utf8_lossy(data).to_string(),
+        DataFormat::Raw => hex::encode(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msrx_tool_error::MsrxToolError;
+    use crate::track_status::TrackStatus;
+
+    #[test]
+    fn test_compare_iso() -> Result<(), MsrxToolError> {
+        let written =
+            TracksData::from_str("%A?_;1?", &'_')?.with_missing_tracks(TrackAction::Erase);
+        let read = TracksData::from_str("%A?_;2?_;3?", &'_')?;
+
+        assert_eq!(
+            compare(&written, &read),
+            vec![
+                TrackMismatch {
+                    track: 2,
+                    expected: ";1?".to_string(),
+                    actual: ";2?".to_string(),
+                },
+                TrackMismatch {
+                    track: 3,
+                    expected: "".to_string(),
+                    actual: ";3?".to_string(),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_skips_tracks_left_as_is() -> Result<(), MsrxToolError> {
+        let written = TracksData::from_str("_;1?", &'_')?;
+        let mut read = TracksData::from_str("%B?_;1?_;3?", &'_')?;
+        read.status = TrackStatus::Ok;
+
+        assert!(compare(&written, &read).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_raw_ignores_zero_padding() -> Result<(), MsrxToolError> {
+        let written = TracksData::from_raw_str("00af0c__ff", &'_')?;
+        let read = TracksData::from_raw_str("0000af0c0000__fe", &'_')?;
+
+        let mismatches = compare(&written, &read);
+
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            mismatches[0].to_string(),
+            "Track 3: expected \"ff\", read \"fe\""
+        );
+        Ok(())
+    }
+}